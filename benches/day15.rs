@@ -0,0 +1,89 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use shuttle_cch23::password::policy::Policy;
+
+const CHARS: &[char] = &[
+    'a', 'b', 'j', 'o', 'y', 'z', 'A', 'Z', '0', '2', '3', '9', '.', ' ', '⦖', '😀', '🥶',
+];
+
+// deterministic mix of passwords failing at every rule, no rng needed
+fn passwords(count: usize) -> Vec<String> {
+    let mut state: u64 = 2023;
+    (0..count)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let len = 4 + (state >> 59) as usize;
+            (0..len)
+                .map(|i| CHARS[((state >> (i % 48)) as usize + i) % CHARS.len()])
+                .collect()
+        })
+        .collect()
+}
+
+fn policies() -> [(&'static str, Policy); 2] {
+    [
+        ("default", Policy::default()),
+        (
+            "strict",
+            toml::from_str(include_str!("../policies/strict.toml")).unwrap(),
+        ),
+    ]
+}
+
+fn batch(c: &mut Criterion) {
+    for (name, policy) in policies() {
+        let mut group = c.benchmark_group(format!("{}_policy", name));
+        for count in [1_000, 10_000, 100_000] {
+            let passwords = passwords(count);
+            group.throughput(Throughput::Elements(count as u64));
+            group.bench_with_input(
+                BenchmarkId::new("first", count),
+                &passwords,
+                |b, passwords| {
+                    b.iter(|| {
+                        passwords
+                            .iter()
+                            .filter(|password| policy.check(black_box(password)).is_ok())
+                            .count()
+                    })
+                },
+            );
+            group.bench_with_input(
+                BenchmarkId::new("all", count),
+                &passwords,
+                |b, passwords| {
+                    b.iter(|| {
+                        passwords
+                            .iter()
+                            .map(|password| policy.violations(black_box(password)).len())
+                            .sum::<usize>()
+                    })
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+// long digit runs used to panic in `parse::<i32>`
+fn integer_sum(c: &mut Criterion) {
+    let policy: Policy =
+        serde_json::from_str(r#"{"rules": [{"rule": "integer_sum", "sum": 2023}]}"#).unwrap();
+    let mut group = c.benchmark_group("integer_sum");
+    for digits in [10, 1_000, 100_000] {
+        let password = format!("Aa{}zZ2023", "9".repeat(digits));
+        group.throughput(Throughput::Bytes(password.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(digits),
+            &password,
+            |b, password| b.iter(|| policy.check(black_box(password)).is_err()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, batch, integer_sum);
+criterion_main!(benches);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS chat_views (
+  id INT PRIMARY KEY,
+  total BIGINT NOT NULL
+);
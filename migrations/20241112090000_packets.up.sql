@@ -0,0 +1,5 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS packets (
+  id TEXT PRIMARY KEY,
+  saved_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use axum::{
+    extract::{Multipart, Query, State},
+    Json,
+};
+use image::{DynamicImage, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use super::{dominant_pixels, read_images, ImageConfig, ImageError};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Channel {
+    const ALL: [Channel; 3] = [Channel::Red, Channel::Green, Channel::Blue];
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Channel::Red => "red",
+            Channel::Green => "green",
+            Channel::Blue => "blue",
+        }
+    }
+}
+
+impl FromStr for Channel {
+    type Err = ImageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "red" | "r" => Ok(Channel::Red),
+            "green" | "g" => Ok(Channel::Green),
+            "blue" | "b" => Ok(Channel::Blue),
+            _ => Err(ImageError::InvalidQuery("unknown channel")),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AnalyzeQuery {
+    // palette size
+    colors: Option<usize>,
+    // histogram buckets per channel
+    bins: Option<usize>,
+    // comma separated channels to count dominance for, all by default
+    dominance: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PaletteColor {
+    color: String,
+    share: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Histogram {
+    red: Vec<u64>,
+    green: Vec<u64>,
+    blue: Vec<u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImageAnalysis {
+    name: String,
+    #[serde(flatten)]
+    stats: ImageStats,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImageStats {
+    width: u32,
+    height: u32,
+    format: Option<String>,
+    // mean luma, 0 is black and 1 is white
+    brightness: f64,
+    palette: Vec<PaletteColor>,
+    histogram: Histogram,
+    dominance: BTreeMap<&'static str, usize>,
+}
+
+const DEFAULT_COLORS: usize = 5;
+const MAX_COLORS: usize = 16;
+const DEFAULT_BINS: usize = 256;
+// k-means runs on at most this many evenly spaced pixels
+const PALETTE_SAMPLES: usize = 10000;
+const PALETTE_ITERATIONS: usize = 20;
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (0..3).map(|c| (a[c] - b[c]).powi(2)).sum()
+}
+
+// deterministic k-means, centroids start at evenly spaced samples
+fn palette(image: &RgbImage, k: usize) -> Vec<PaletteColor> {
+    let total = image.width() as usize * image.height() as usize;
+    if total == 0 {
+        return Vec::new();
+    }
+    let samples: Vec<[f64; 3]> = image
+        .pixels()
+        .step_by(total.div_ceil(PALETTE_SAMPLES))
+        .map(|pixel| [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64])
+        .collect();
+    let k = k.min(samples.len());
+
+    let mut centroids: Vec<[f64; 3]> = (0..k).map(|i| samples[i * samples.len() / k]).collect();
+    let mut assignment = vec![0; samples.len()];
+    for _ in 0..PALETTE_ITERATIONS {
+        let mut changed = false;
+        for (sample, assigned) in samples.iter().zip(assignment.iter_mut()) {
+            let nearest = (0..k)
+                .min_by(|&a, &b| {
+                    distance(sample, &centroids[a]).total_cmp(&distance(sample, &centroids[b]))
+                })
+                .unwrap_or_default();
+            changed |= nearest != *assigned;
+            *assigned = nearest;
+        }
+
+        let mut sums = vec![([0.0; 3], 0usize); k];
+        for (sample, &assigned) in samples.iter().zip(&assignment) {
+            let (sum, count) = &mut sums[assigned];
+            (0..3).for_each(|c| sum[c] += sample[c]);
+            *count += 1;
+        }
+        for (centroid, (sum, count)) in centroids.iter_mut().zip(sums) {
+            if count > 0 {
+                *centroid = sum.map(|value| value / count as f64);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut counts = vec![0usize; k];
+    assignment
+        .iter()
+        .for_each(|&assigned| counts[assigned] += 1);
+    let mut palette: Vec<PaletteColor> = centroids
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(centroid, count)| PaletteColor {
+            color: format!(
+                "#{:02x}{:02x}{:02x}",
+                centroid[0].round() as u8,
+                centroid[1].round() as u8,
+                centroid[2].round() as u8
+            ),
+            share: count as f64 / samples.len() as f64,
+        })
+        .collect();
+    palette.sort_by(|a, b| b.share.total_cmp(&a.share));
+    palette
+}
+
+fn histogram(image: &RgbImage, bins: usize) -> Histogram {
+    let mut channels = [vec![0; bins], vec![0; bins], vec![0; bins]];
+    for pixel in image.pixels() {
+        for (c, channel) in channels.iter_mut().enumerate() {
+            channel[pixel[c] as usize * bins / 256] += 1;
+        }
+    }
+    let [red, green, blue] = channels;
+    Histogram { red, green, blue }
+}
+
+fn brightness(image: &DynamicImage) -> f64 {
+    let luma = image.to_luma8();
+    let total: u64 = luma.pixels().map(|pixel| pixel[0] as u64).sum();
+    let count = luma.width() as u64 * luma.height() as u64;
+    if count == 0 {
+        return 0.0;
+    }
+    total as f64 / count as f64 / 255.0
+}
+
+pub async fn analyze(
+    State(config): State<ImageConfig>,
+    Query(query): Query<AnalyzeQuery>,
+    multipart: Multipart,
+) -> Result<Json<Vec<ImageAnalysis>>, ImageError> {
+    let colors = query.colors.unwrap_or(DEFAULT_COLORS).clamp(1, MAX_COLORS);
+    let bins = query.bins.unwrap_or(DEFAULT_BINS).clamp(1, 256);
+    let channels = match &query.dominance {
+        Some(channels) => channels
+            .split(',')
+            .map(Channel::from_str)
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Channel::ALL.to_vec(),
+    };
+
+    let fields = read_images(multipart, &config, move |upload| {
+        let rgb = upload.image.to_rgb8();
+        let rgb32f = upload.image.to_rgb32f();
+        ImageStats {
+            width: upload.image.width(),
+            height: upload.image.height(),
+            format: upload
+                .format
+                .map(|format| format!("{:?}", format).to_lowercase()),
+            brightness: brightness(&upload.image),
+            palette: palette(&rgb, colors),
+            histogram: histogram(&rgb, bins),
+            dominance: channels
+                .iter()
+                .map(|channel| (channel.name(), dominant_pixels(&rgb32f, channel.index())))
+                .collect(),
+        }
+    })
+    .await?;
+    let analyses = fields
+        .into_iter()
+        .map(|field| {
+            Ok(ImageAnalysis {
+                name: field.name,
+                stats: field.result?,
+            })
+        })
+        .collect::<Result<_, ImageError>>()?;
+    Ok(Json(analyses))
+}
@@ -0,0 +1,490 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+
+use askama::Template;
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use futures_util::StreamExt;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Serialize;
+use shuttle_runtime::SecretStore;
+use tempfile::NamedTempFile;
+use tokio::io::AsyncWriteExt;
+use tower_http::services::ServeDir;
+
+use crate::util::{constant_time_eq, content_length, parse_secret};
+
+#[derive(Clone, Debug)]
+pub struct AssetConfig {
+    pub dir: PathBuf,
+    // Cache-Control max-age of served assets
+    pub max_age: u64,
+    // html index for directories without an index.html
+    pub listing: bool,
+    // bearer token for the management endpoints, disabled when unset
+    pub token: Option<String>,
+    pub max_upload_size: u64,
+}
+
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("assets"),
+            max_age: 3600,
+            listing: false,
+            token: None,
+            max_upload_size: 32 * 1024 * 1024,
+        }
+    }
+}
+
+impl AssetConfig {
+    pub fn from_secrets(secrets: &SecretStore) -> anyhow::Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            dir: secrets.get("ASSETS_DIR").map_or(default.dir, PathBuf::from),
+            max_age: parse_secret(secrets, "ASSETS_MAX_AGE", default.max_age)?,
+            listing: parse_secret(secrets, "ASSETS_LISTING", default.listing)?,
+            token: secrets
+                .get("ASSETS_TOKEN")
+                .filter(|token| !token.is_empty()),
+            max_upload_size: parse_secret(
+                secrets,
+                "ASSETS_MAX_UPLOAD_SIZE",
+                default.max_upload_size,
+            )?,
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AssetError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("asset task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("invalid upload body: {0}")]
+    Body(#[from] axum::Error),
+    #[error("asset management is disabled")]
+    Disabled,
+    #[error("missing or wrong bearer token")]
+    Unauthorized,
+    #[error("unsafe path: {0}")]
+    UnsafePath(String),
+    #[error("upload larger than {0} bytes")]
+    TooLarge(u64),
+    #[error("asset not found")]
+    NotFound,
+}
+
+impl IntoResponse for AssetError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            AssetError::Io(_) | AssetError::Task(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AssetError::Body(_) | AssetError::UnsafePath(_) => StatusCode::BAD_REQUEST,
+            AssetError::Disabled => StatusCode::FORBIDDEN,
+            AssetError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AssetError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AssetError::NotFound => StatusCode::NOT_FOUND,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub fn routes(config: AssetConfig) -> Router {
+    // `foo.js.gz` and `foo.js.br` are sent in place of `foo.js` when accepted
+    let files = ServeDir::new(&config.dir)
+        .precompressed_gzip()
+        .precompressed_br()
+        .fallback(get(listing).with_state(config.clone()));
+
+    Router::new()
+        .route("/asset_files", get(list))
+        .route("/asset_files/*path", put(upload).delete(delete))
+        .nest_service(
+            "/assets",
+            Router::new()
+                .fallback_service(files)
+                .layer(middleware::from_fn_with_state(
+                    config.clone(),
+                    cache_headers,
+                )),
+        )
+        .with_state(config)
+}
+
+// precompressed variants differ in length, so they get their own tag
+fn etag(headers: &HeaderMap) -> Option<String> {
+    let length = headers.get(header::CONTENT_LENGTH)?.to_str().ok()?;
+    let modified = headers.get(header::LAST_MODIFIED)?.to_str().ok()?;
+    let modified = DateTime::parse_from_rfc2822(modified).ok()?.timestamp();
+    Some(format!("W/\"{}-{:x}\"", length, modified))
+}
+
+// weak comparison, the only one allowed for If-None-Match
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+async fn cache_headers(State(config): State<AssetConfig>, req: Request, next: Next) -> Response {
+    let if_none_match = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let mut response = next.run(req).await;
+
+    let status = response.status();
+    if !status.is_success() && status != StatusCode::NOT_MODIFIED {
+        return response;
+    }
+    let etag = (status == StatusCode::OK)
+        .then(|| etag(response.headers()))
+        .flatten();
+    let headers = response.headers_mut();
+    if !headers.contains_key(header::CACHE_CONTROL) {
+        if let Ok(value) = HeaderValue::from_str(&format!("public, max-age={}", config.max_age)) {
+            headers.insert(header::CACHE_CONTROL, value);
+        }
+    }
+    headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    let Some(etag) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) else {
+        return response;
+    };
+    headers.insert(header::ETAG, etag.clone());
+
+    let fresh = if_none_match
+        .zip(etag.to_str().ok())
+        .is_some_and(|(if_none_match, etag)| etag_matches(&if_none_match, etag));
+    if fresh {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        for name in [
+            header::ETAG,
+            header::CACHE_CONTROL,
+            header::VARY,
+            header::LAST_MODIFIED,
+        ] {
+            if let Some(value) = response.headers().get(&name) {
+                not_modified.headers_mut().insert(name, value.clone());
+            }
+        }
+        return not_modified;
+    }
+    response
+}
+
+// only plain names below the asset dir, no `..`, roots or empty paths
+fn resolve(dir: &Path, path: &str) -> Result<PathBuf, AssetError> {
+    let relative = Path::new(path.trim_matches('/'));
+    let safe = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !safe {
+        return Err(AssetError::UnsafePath(path.to_string()));
+    }
+    Ok(dir.join(relative))
+}
+
+fn authorize(config: &AssetConfig, headers: &HeaderMap) -> Result<(), AssetError> {
+    let token = config.token.as_ref().ok_or(AssetError::Disabled)?;
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AssetError::Unauthorized)?;
+    if !constant_time_eq(given.as_bytes(), token.as_bytes()) {
+        return Err(AssetError::Unauthorized);
+    }
+    Ok(())
+}
+
+fn format_time(time: io::Result<SystemTime>) -> String {
+    time.map(|time| DateTime::<Utc>::from(time).to_rfc3339())
+        .unwrap_or_default()
+}
+
+// keeps the characters that never need escaping in a path segment
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'.')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'~');
+
+struct ListingEntry {
+    name: String,
+    href: String,
+    size: String,
+    modified: String,
+}
+
+#[derive(Template)]
+#[template(path = "day11_listing.html")]
+struct ListingTemplate {
+    path: String,
+    entries: Vec<ListingEntry>,
+}
+
+fn read_listing(dir: &Path) -> io::Result<Vec<ListingEntry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if is_variant(&entry.path()) {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let href = utf8_percent_encode(&name, SEGMENT).to_string();
+        let (name, href, size) = if metadata.is_dir() {
+            (format!("{}/", name), format!("{}/", href), String::new())
+        } else {
+            (name, href, metadata.len().to_string())
+        };
+        entries.push(ListingEntry {
+            name,
+            href,
+            size,
+            modified: format_time(metadata.modified()),
+        });
+    }
+    // directories first
+    entries
+        .sort_by(|a, b| (!a.name.ends_with('/'), &a.name).cmp(&(!b.name.ends_with('/'), &b.name)));
+    Ok(entries)
+}
+
+// ServeDir falls back here when nothing matched, directories included;
+// listings change with every upload, so clients revalidate them
+async fn listing(
+    State(config): State<AssetConfig>,
+    uri: Uri,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !config.listing {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let path = percent_decode_str(uri.path())
+        .decode_utf8()
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let dir = if path.trim_matches('/').is_empty() {
+        config.dir.clone()
+    } else {
+        resolve(&config.dir, &path).map_err(|_| StatusCode::NOT_FOUND)?
+    };
+    let entries = tokio::task::spawn_blocking(move || read_listing(&dir))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let template = ListingTemplate {
+        path: path.into_owned(),
+        entries,
+    };
+    let html = template
+        .render()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(([(header::CACHE_CONTROL, "no-cache")], Html(html)))
+}
+
+const VARIANTS: [(&str, &str); 2] = [("gzip", "gz"), ("br", "br")];
+
+fn variant_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+// variants are reported with the asset they belong to
+fn is_variant(path: &Path) -> bool {
+    VARIANTS.iter().any(|(_, extension)| {
+        path.extension().is_some_and(|e| e == *extension) && path.with_extension("").is_file()
+    })
+}
+
+// formats that are already compressed gain nothing from gzip
+fn is_compressible(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    matches!(
+        extension.as_deref(),
+        Some(
+            "html"
+                | "htm"
+                | "css"
+                | "js"
+                | "mjs"
+                | "json"
+                | "map"
+                | "svg"
+                | "txt"
+                | "md"
+                | "xml"
+                | "csv"
+                | "wasm"
+                | "ico"
+        )
+    )
+}
+
+#[derive(Serialize, Debug)]
+pub struct AssetInfo {
+    path: String,
+    size: u64,
+    modified: String,
+    // encodings with a precompressed variant next to the asset
+    precompressed: Vec<&'static str>,
+}
+
+fn asset_info(dir: &Path, path: &Path) -> Result<AssetInfo, AssetError> {
+    let metadata = fs::metadata(path)?;
+    Ok(AssetInfo {
+        path: path
+            .strip_prefix(dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned(),
+        size: metadata.len(),
+        modified: format_time(metadata.modified()),
+        precompressed: VARIANTS
+            .iter()
+            .filter(|(_, extension)| variant_path(path, extension).is_file())
+            .map(|(encoding, _)| *encoding)
+            .collect(),
+    })
+}
+
+fn collect_assets(
+    dir: &Path,
+    current: &Path,
+    assets: &mut Vec<AssetInfo>,
+) -> Result<(), AssetError> {
+    for entry in fs::read_dir(current)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_assets(dir, &path, assets)?;
+            continue;
+        }
+        if !is_variant(&path) {
+            assets.push(asset_info(dir, &path)?);
+        }
+    }
+    Ok(())
+}
+
+pub async fn list(
+    State(config): State<AssetConfig>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<AssetInfo>>, AssetError> {
+    authorize(&config, &headers)?;
+    let assets = tokio::task::spawn_blocking(move || {
+        let mut assets = Vec::new();
+        if config.dir.is_dir() {
+            collect_assets(&config.dir, &config.dir, &mut assets)?;
+        }
+        assets.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok::<_, AssetError>(assets)
+    })
+    .await??;
+    Ok(Json(assets))
+}
+
+fn write_gzip(path: &Path) -> Result<(), AssetError> {
+    let target = variant_path(path, "gz");
+    let parent = target.parent().unwrap_or(Path::new("."));
+    let temp = NamedTempFile::new_in(parent)?;
+    let mut encoder = GzEncoder::new(temp, Compression::best());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    let mut temp = encoder.finish()?;
+    temp.flush()?;
+    temp.persist(target).map_err(|e| e.error)?;
+    Ok(())
+}
+
+// written next to the target and renamed, readers never see a partial file;
+// stale variants are replaced by a fresh gzip or removed
+pub async fn upload(
+    State(config): State<AssetConfig>,
+    axum::extract::Path(path): axum::extract::Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, Json<AssetInfo>), AssetError> {
+    authorize(&config, &headers)?;
+    let target = resolve(&config.dir, &path)?;
+    if target == config.dir {
+        return Err(AssetError::UnsafePath(path));
+    }
+    if content_length(&headers).is_some_and(|length| length > config.max_upload_size) {
+        return Err(AssetError::TooLarge(config.max_upload_size));
+    }
+
+    let parent = target.parent().unwrap_or(&config.dir).to_path_buf();
+    tokio::fs::create_dir_all(&parent).await?;
+    let (file, temp_path) = NamedTempFile::new_in(&parent)?.into_parts();
+    let mut file = tokio::fs::File::from_std(file);
+    let mut written = 0;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        written += chunk.len() as u64;
+        if written > config.max_upload_size {
+            return Err(AssetError::TooLarge(config.max_upload_size));
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.sync_all().await?;
+    temp_path.persist(&target).map_err(|e| e.error)?;
+
+    let dir = config.dir.clone();
+    let info = tokio::task::spawn_blocking(move || {
+        for (_, extension) in VARIANTS {
+            let variant = variant_path(&target, extension);
+            if variant.is_file() {
+                fs::remove_file(variant)?;
+            }
+        }
+        if is_compressible(&target) {
+            write_gzip(&target)?;
+        }
+        asset_info(&dir, &target)
+    })
+    .await??;
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+pub async fn delete(
+    State(config): State<AssetConfig>,
+    axum::extract::Path(path): axum::extract::Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AssetError> {
+    authorize(&config, &headers)?;
+    let target = resolve(&config.dir, &path)?;
+    if !tokio::fs::metadata(&target)
+        .await
+        .is_ok_and(|metadata| metadata.is_file())
+    {
+        return Err(AssetError::NotFound);
+    }
+    tokio::fs::remove_file(&target).await?;
+    for (_, extension) in VARIANTS {
+        // a missing variant is fine
+        let _ = tokio::fs::remove_file(variant_path(&target, extension)).await;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,291 @@
+use std::{collections::BTreeMap, io::Cursor};
+
+use assets::AssetConfig;
+use axum::{
+    extract::{
+        multipart::{self, MultipartError},
+        DefaultBodyLimit, Multipart, Query, State,
+    },
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use image::{DynamicImage, ImageFormat, ImageReader, Limits, Rgb, Rgb32FImage};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use shuttle_runtime::SecretStore;
+
+use crate::util::parse_secret;
+
+pub mod analyze;
+pub mod assets;
+pub mod transform;
+
+#[derive(Clone, Debug)]
+pub struct ImageConfig {
+    // whole multipart request
+    pub max_request_size: usize,
+    // per uploaded image
+    pub max_file_size: usize,
+    // for decoded images and requested output sizes
+    pub max_width: u32,
+    pub max_height: u32,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            max_request_size: 32 * 1024 * 1024,
+            max_file_size: 10 * 1024 * 1024,
+            max_width: 8192,
+            max_height: 8192,
+        }
+    }
+}
+
+impl ImageConfig {
+    pub fn from_secrets(secrets: &SecretStore) -> anyhow::Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            max_request_size: parse_secret(
+                secrets,
+                "IMAGE_MAX_REQUEST_SIZE",
+                default.max_request_size,
+            )?,
+            max_file_size: parse_secret(secrets, "IMAGE_MAX_FILE_SIZE", default.max_file_size)?,
+            max_width: parse_secret(secrets, "IMAGE_MAX_WIDTH", default.max_width)?,
+            max_height: parse_secret(secrets, "IMAGE_MAX_HEIGHT", default.max_height)?,
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ImageError {
+    #[error("invalid multipart body: {0}")]
+    Multipart(#[from] MultipartError),
+    #[error("no image uploaded")]
+    NoImage,
+    #[error("image larger than {0} bytes")]
+    FileTooLarge(usize),
+    #[error("image exceeds {0}x{1} pixels")]
+    TooManyPixels(u32, u32),
+    #[error("cannot decode image: {0}")]
+    Decode(image::ImageError),
+    #[error("cannot encode image: {0}")]
+    Encode(image::ImageError),
+    // a valid image the negotiated format has no encoding for
+    #[error("image cannot be converted: {0}")]
+    Unencodable(image::ImageError),
+    #[error("invalid query: {0}")]
+    InvalidQuery(&'static str),
+    #[error("none of the accepted media types can be produced")]
+    NotAcceptable,
+    #[error("image task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl IntoResponse for ImageError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ImageError::Multipart(ref e) => e.status(),
+            ImageError::NoImage | ImageError::Decode(_) | ImageError::InvalidQuery(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            ImageError::FileTooLarge(_) | ImageError::TooManyPixels(..) => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            ImageError::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            ImageError::Unencodable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ImageError::Encode(_) | ImageError::Task(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub fn routes(config: ImageConfig, assets: AssetConfig) -> Router {
+    Router::new()
+        .route("/red_pixels", post(task2))
+        .route("/analyze", post(analyze::analyze))
+        .route("/resize", post(transform::resize))
+        .route("/thumbnail", post(transform::thumbnail))
+        .route("/crop", post(transform::crop))
+        .route("/rotate", post(transform::rotate))
+        .route("/flip", post(transform::flip))
+        .route("/grayscale", post(transform::grayscale))
+        .route("/red_mask", post(transform::red_mask))
+        .layer(DefaultBodyLimit::max(config.max_request_size))
+        .with_state(config)
+        .merge(assets::routes(assets))
+}
+
+pub struct Upload {
+    pub format: Option<ImageFormat>,
+    pub image: DynamicImage,
+}
+
+pub struct Field<T> {
+    // file name of the field, its field name otherwise
+    pub name: String,
+    pub result: Result<T, ImageError>,
+}
+
+// width and height are checked from the header before any pixel is decoded
+fn decode(data: &[u8], config: &ImageConfig) -> Result<Upload, ImageError> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| ImageError::Decode(e.into()))?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(config.max_width);
+    limits.max_image_height = Some(config.max_height);
+    reader.limits(limits);
+
+    let format = reader.format();
+    let image = reader.decode().map_err(|e| match e {
+        image::ImageError::Limits(_) => {
+            ImageError::TooManyPixels(config.max_width, config.max_height)
+        }
+        e => ImageError::Decode(e),
+    })?;
+    Ok(Upload { format, image })
+}
+
+// the bytes of a field, `None` once it exceeds the file size limit
+async fn read_data(
+    field: &mut multipart::Field<'_>,
+    config: &ImageConfig,
+) -> Result<Option<Vec<u8>>, ImageError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.chunk().await? {
+        if data.len() + chunk.len() > config.max_file_size {
+            // the rest of the field is skipped by the next `next_field`
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Some(data))
+}
+
+// every image is decoded and handed to `f` on a blocking thread before the
+// next field is read, so only one decoded image is held at a time; a field
+// that is too large or cannot be decoded only fails itself, a broken
+// multipart body fails the whole request
+pub async fn read_images<T, F>(
+    mut multipart: Multipart,
+    config: &ImageConfig,
+    f: F,
+) -> Result<Vec<Field<T>>, ImageError>
+where
+    T: Send + 'static,
+    F: Fn(Upload) -> T + Clone + Send + 'static,
+{
+    let mut fields = Vec::new();
+    while let Some(mut field) = multipart.next_field().await? {
+        let name = field
+            .file_name()
+            .or(field.name())
+            .unwrap_or_default()
+            .to_string();
+        let result = match read_data(&mut field, config).await? {
+            Some(data) => {
+                let config = config.clone();
+                let f = f.clone();
+                tokio::task::spawn_blocking(move || decode(&data, &config).map(f)).await?
+            }
+            None => Err(ImageError::FileTooLarge(config.max_file_size)),
+        };
+        fields.push(Field { name, result });
+    }
+    Ok(fields)
+}
+
+// first image of the upload for endpoints working on a single one, later
+// fields are not read
+pub async fn read_image(
+    mut multipart: Multipart,
+    config: &ImageConfig,
+) -> Result<Upload, ImageError> {
+    let mut field = multipart.next_field().await?.ok_or(ImageError::NoImage)?;
+    let data = read_data(&mut field, config)
+        .await?
+        .ok_or(ImageError::FileTooLarge(config.max_file_size))?;
+    let config = config.clone();
+    tokio::task::spawn_blocking(move || decode(&data, &config)).await?
+}
+
+// `channel` exceeds the other two combined, task2 counts red
+pub fn is_dominant(pixel: &Rgb<f32>, channel: usize) -> bool {
+    let others: f32 = (0..3).filter(|&c| c != channel).map(|c| pixel[c]).sum();
+    pixel[channel] > others
+}
+
+pub fn dominant_pixels(image: &Rgb32FImage, channel: usize) -> usize {
+    image
+        .pixels()
+        .filter(|pixel| is_dominant(pixel, channel))
+        .count()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedPixelsQuery {
+    // json with a result per field instead of the plain total
+    #[serde(default)]
+    per_field: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum FieldResult {
+    Count { red_pixels: usize },
+    Error { error: String },
+}
+
+#[derive(Serialize, Debug)]
+pub struct RedPixels {
+    total: usize,
+    fields: BTreeMap<String, FieldResult>,
+}
+
+pub async fn task2(
+    State(config): State<ImageConfig>,
+    Query(query): Query<RedPixelsQuery>,
+    multipart: Multipart,
+) -> Result<Response, ImageError> {
+    let fields = read_images(multipart, &config, |upload| {
+        dominant_pixels(&upload.image.to_rgb32f(), 0)
+    })
+    .await?;
+
+    // the plain total still fails on the first bad field
+    if !query.per_field {
+        let mut counts = 0;
+        for field in fields {
+            counts += field.result?;
+        }
+        return Ok(counts.to_string().into_response());
+    }
+
+    let mut result = RedPixels {
+        total: 0,
+        fields: BTreeMap::new(),
+    };
+    for field in fields {
+        let field_result = match field.result {
+            Ok(red_pixels) => {
+                result.total += red_pixels;
+                FieldResult::Count { red_pixels }
+            }
+            Err(e) => FieldResult::Error {
+                error: e.to_string(),
+            },
+        };
+        // repeated names get their occurrence appended, `a.png#2`
+        let mut key = field.name.clone();
+        let mut n = 1;
+        while result.fields.contains_key(&key) {
+            n += 1;
+            key = format!("{}#{}", field.name, n);
+        }
+        result.fields.insert(key, field_result);
+    }
+    Ok(Json(result).into_response())
+}
@@ -0,0 +1,359 @@
+use std::io::Cursor;
+
+use axum::{
+    extract::{Multipart, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use image::{imageops::FilterType, DynamicImage, GrayImage, ImageFormat, Luma};
+use serde::Deserialize;
+
+use super::{is_dominant, read_image, ImageConfig, ImageError};
+
+// formats we encode, in order of preference for wildcards
+const OUTPUT_FORMATS: [ImageFormat; 5] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::WebP,
+    ImageFormat::Gif,
+    ImageFormat::Bmp,
+];
+
+// the media range with the highest q wins, the earlier one on a tie; `q=0`
+// refuses a format, wildcards keep the input format when it can be encoded
+fn negotiate(headers: &HeaderMap, input: Option<ImageFormat>) -> Result<ImageFormat, ImageError> {
+    let fallback = input
+        .filter(|format| OUTPUT_FORMATS.contains(format))
+        .unwrap_or(ImageFormat::Png);
+    let Some(accept) = headers.get(header::ACCEPT) else {
+        return Ok(fallback);
+    };
+    let Ok(accept) = accept.to_str() else {
+        return Err(ImageError::NotAcceptable);
+    };
+
+    let mut ranges = Vec::new();
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let media_type = params.next().unwrap_or_default().trim();
+        let q = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .map_or(Some(1.0), |(_, value)| value.trim().parse::<f32>().ok());
+        // a malformed q ignores the range
+        if let Some(q) = q.filter(|q| (0.0..=1.0).contains(q)) {
+            ranges.push((media_type, q));
+        }
+    }
+    let refused: Vec<ImageFormat> = OUTPUT_FORMATS
+        .into_iter()
+        .filter(|format| {
+            ranges
+                .iter()
+                .any(|&(media_type, q)| q == 0.0 && media_type == format.to_mime_type())
+        })
+        .collect();
+    let wildcard = std::iter::once(fallback)
+        .chain(OUTPUT_FORMATS)
+        .find(|format| !refused.contains(format));
+
+    let mut best: Option<(ImageFormat, f32)> = None;
+    for (media_type, q) in ranges {
+        if q == 0.0 || best.is_some_and(|(_, best)| q <= best) {
+            continue;
+        }
+        let format = match media_type {
+            "*/*" | "image/*" => wildcard,
+            _ => OUTPUT_FORMATS
+                .into_iter()
+                .find(|format| format.to_mime_type() == media_type),
+        };
+        if let Some(format) = format {
+            best = Some((format, q));
+        }
+    }
+    best.map(|(format, _)| format)
+        .ok_or(ImageError::NotAcceptable)
+}
+
+fn encode(image: DynamicImage, format: ImageFormat) -> Result<Vec<u8>, ImageError> {
+    // jpeg has no alpha channel, webp only takes 8 bit rgb(a), gif only rgba,
+    // bmp only 8 bit and png up to 16 bit
+    let image = match (format, image) {
+        (ImageFormat::Jpeg, image) => DynamicImage::ImageRgb8(image.to_rgb8()),
+        (ImageFormat::WebP | ImageFormat::Gif, image) => DynamicImage::ImageRgba8(image.to_rgba8()),
+        (ImageFormat::Bmp, image) if image.color().has_alpha() => {
+            DynamicImage::ImageRgba8(image.to_rgba8())
+        }
+        (ImageFormat::Bmp, image) => DynamicImage::ImageRgb8(image.to_rgb8()),
+        (ImageFormat::Png, image @ DynamicImage::ImageRgb32F(_)) => {
+            DynamicImage::ImageRgb16(image.to_rgb16())
+        }
+        (ImageFormat::Png, image @ DynamicImage::ImageRgba32F(_)) => {
+            DynamicImage::ImageRgba16(image.to_rgba16())
+        }
+        (_, image) => image,
+    };
+    let mut buf = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut buf), format)
+        .map_err(|e| match e {
+            image::ImageError::Unsupported(_) => ImageError::Unencodable(e),
+            e => ImageError::Encode(e),
+        })?;
+    Ok(buf)
+}
+
+// applies `f` to the first image of the upload
+async fn transform<F>(
+    config: &ImageConfig,
+    headers: HeaderMap,
+    multipart: Multipart,
+    f: F,
+) -> Result<Response, ImageError>
+where
+    F: FnOnce(DynamicImage) -> Result<DynamicImage, ImageError> + Send + 'static,
+{
+    let upload = read_image(multipart, config).await?;
+    let format = negotiate(&headers, upload.format)?;
+    let body = tokio::task::spawn_blocking(move || encode(f(upload.image)?, format)).await??;
+    Ok(([(header::CONTENT_TYPE, format.to_mime_type())], body).into_response())
+}
+
+// requested output sizes are bound like decoded images
+fn check_dimension(value: u32, max: u32) -> Result<u32, ImageError> {
+    if value == 0 || value > max {
+        return Err(ImageError::InvalidQuery("size out of range"));
+    }
+    Ok(value)
+}
+
+// the size `image` scales `width`x`height` to keeping the aspect ratio, the
+// math of image's `resize_dimensions` without its clamping to u32::MAX
+fn resize_dimensions(width: u32, height: u32, nwidth: u32, nheight: u32, fill: bool) -> (u64, u64) {
+    let wratio = f64::from(nwidth) / f64::from(width);
+    let hratio = f64::from(nheight) / f64::from(height);
+    let ratio = if fill {
+        wratio.max(hratio)
+    } else {
+        wratio.min(hratio)
+    };
+    (
+        ((f64::from(width) * ratio).round() as u64).max(1),
+        ((f64::from(height) * ratio).round() as u64).max(1),
+    )
+}
+
+// buffers are allocated before any pixel is resampled, so sizes derived from
+// the aspect ratio are checked before resizing
+fn check_size(config: &ImageConfig, (width, height): (u64, u64)) -> Result<(u32, u32), ImageError> {
+    let (max_width, max_height) = (u64::from(config.max_width), u64::from(config.max_height));
+    if width > max_width || height > max_height || width * height > max_width * max_height {
+        return Err(ImageError::TooManyPixels(
+            config.max_width,
+            config.max_height,
+        ));
+    }
+    Ok((width as u32, height as u32))
+}
+
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    // within the box keeping the aspect ratio
+    #[default]
+    Fit,
+    // covers the box keeping the aspect ratio, the overflow is cropped
+    Fill,
+    // exactly the box, distorting if needed
+    Exact,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResizeQuery {
+    width: Option<u32>,
+    height: Option<u32>,
+    #[serde(default)]
+    mode: ResizeMode,
+}
+
+pub async fn resize(
+    State(config): State<ImageConfig>,
+    headers: HeaderMap,
+    Query(query): Query<ResizeQuery>,
+    multipart: Multipart,
+) -> Result<Response, ImageError> {
+    let width = query
+        .width
+        .map(|width| check_dimension(width, config.max_width))
+        .transpose()?;
+    let height = query
+        .height
+        .map(|height| check_dimension(height, config.max_height))
+        .transpose()?;
+    let limits = config.clone();
+    transform(&config, headers, multipart, move |image| {
+        match (query.mode, width, height) {
+            (_, None, None) => Err(ImageError::InvalidQuery("width or height required")),
+            (ResizeMode::Fit, width, height) => {
+                let (width, height) = check_size(
+                    &limits,
+                    resize_dimensions(
+                        image.width(),
+                        image.height(),
+                        width.unwrap_or(u32::MAX),
+                        height.unwrap_or(u32::MAX),
+                        false,
+                    ),
+                )?;
+                Ok(image.resize_exact(width, height, FilterType::Lanczos3))
+            }
+            (ResizeMode::Fill, Some(width), Some(height)) => {
+                // scaled to cover the box before the overflow is cropped
+                check_size(
+                    &limits,
+                    resize_dimensions(image.width(), image.height(), width, height, true),
+                )?;
+                Ok(image.resize_to_fill(width, height, FilterType::Lanczos3))
+            }
+            (ResizeMode::Exact, Some(width), Some(height)) => {
+                Ok(image.resize_exact(width, height, FilterType::Lanczos3))
+            }
+            _ => Err(ImageError::InvalidQuery("width and height required")),
+        }
+    })
+    .await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ThumbnailQuery {
+    size: Option<u32>,
+}
+
+const DEFAULT_THUMBNAIL_SIZE: u32 = 128;
+
+pub async fn thumbnail(
+    State(config): State<ImageConfig>,
+    headers: HeaderMap,
+    Query(query): Query<ThumbnailQuery>,
+    multipart: Multipart,
+) -> Result<Response, ImageError> {
+    let size = check_dimension(
+        query.size.unwrap_or(DEFAULT_THUMBNAIL_SIZE),
+        config.max_width.min(config.max_height),
+    )?;
+    transform(&config, headers, multipart, move |image| {
+        Ok(image.thumbnail(size, size))
+    })
+    .await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CropQuery {
+    #[serde(default)]
+    x: u32,
+    #[serde(default)]
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+pub async fn crop(
+    State(config): State<ImageConfig>,
+    headers: HeaderMap,
+    Query(query): Query<CropQuery>,
+    multipart: Multipart,
+) -> Result<Response, ImageError> {
+    transform(&config, headers, multipart, move |image| {
+        // the box has to lie within the image
+        let fits = |offset: u32, length: u32, bound: u32| {
+            length > 0 && offset.checked_add(length).is_some_and(|end| end <= bound)
+        };
+        if !fits(query.x, query.width, image.width())
+            || !fits(query.y, query.height, image.height())
+        {
+            return Err(ImageError::InvalidQuery("crop box outside the image"));
+        }
+        Ok(image.crop_imm(query.x, query.y, query.width, query.height))
+    })
+    .await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RotateQuery {
+    // clockwise, multiples of 90
+    degrees: i32,
+}
+
+pub async fn rotate(
+    State(config): State<ImageConfig>,
+    headers: HeaderMap,
+    Query(query): Query<RotateQuery>,
+    multipart: Multipart,
+) -> Result<Response, ImageError> {
+    transform(&config, headers, multipart, move |image| {
+        match query.degrees.rem_euclid(360) {
+            0 => Ok(image),
+            90 => Ok(image.rotate90()),
+            180 => Ok(image.rotate180()),
+            270 => Ok(image.rotate270()),
+            _ => Err(ImageError::InvalidQuery("degrees must be a multiple of 90")),
+        }
+    })
+    .await
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FlipDirection {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FlipQuery {
+    direction: FlipDirection,
+}
+
+pub async fn flip(
+    State(config): State<ImageConfig>,
+    headers: HeaderMap,
+    Query(query): Query<FlipQuery>,
+    multipart: Multipart,
+) -> Result<Response, ImageError> {
+    transform(&config, headers, multipart, move |image| {
+        match query.direction {
+            FlipDirection::Horizontal => Ok(image.fliph()),
+            FlipDirection::Vertical => Ok(image.flipv()),
+        }
+    })
+    .await
+}
+
+pub async fn grayscale(
+    State(config): State<ImageConfig>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Response, ImageError> {
+    transform(&config, headers, multipart, |image| Ok(image.grayscale())).await
+}
+
+// white where task2 counts a red pixel, black everywhere else
+pub async fn red_mask(
+    State(config): State<ImageConfig>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Response, ImageError> {
+    transform(&config, headers, multipart, |image| {
+        let rgb = image.to_rgb32f();
+        let mask = GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+            if is_dominant(rgb.get_pixel(x, y), 0) {
+                Luma([255])
+            } else {
+                Luma([0])
+            }
+        });
+        Ok(DynamicImage::ImageLuma8(mask))
+    })
+    .await
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+
+use axum::Json;
+use chrono::{DateTime, Datelike, FixedOffset, Offset, SecondsFormat, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use super::{parse_ulids, UlidError};
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    // calendar buckets, `2023`, `2023-12`, `2023-12-24`, `2023-12-24T18`
+    Year,
+    Month,
+    Day,
+    Hour,
+    // day of the week regardless of date, `monday`
+    Weekday,
+}
+
+impl GroupBy {
+    fn key(&self, dt: &DateTime<FixedOffset>) -> String {
+        match self {
+            GroupBy::Year => dt.format("%Y").to_string(),
+            GroupBy::Month => dt.format("%Y-%m").to_string(),
+            GroupBy::Day => dt.format("%Y-%m-%d").to_string(),
+            GroupBy::Hour => dt.format("%Y-%m-%dT%H").to_string(),
+            GroupBy::Weekday => dt.format("%A").to_string().to_lowercase(),
+        }
+    }
+}
+
+// every given field has to match, an empty predicate matches everything
+#[derive(Deserialize, Default, Debug)]
+pub struct Predicate {
+    year: Option<i32>,
+    month: Option<u32>,
+    day: Option<u32>,
+    hour: Option<u32>,
+    // 0 is monday, as in `/12/ulids/:weekday`
+    weekday: Option<u32>,
+    // rfc3339 or `now`, both exclusive
+    after: Option<String>,
+    before: Option<String>,
+    // least significant bit of the randomness
+    lsb: Option<u8>,
+}
+
+struct Bounds {
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+}
+
+fn parse_bound(
+    bound: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, UlidError> {
+    match bound {
+        None => Ok(None),
+        Some("now") => Ok(Some(now)),
+        Some(bound) => DateTime::parse_from_rfc3339(bound)
+            .map(|dt| Some(dt.to_utc()))
+            .map_err(|_| UlidError::InvalidTime(bound.to_string())),
+    }
+}
+
+impl Predicate {
+    fn bounds(&self, now: DateTime<Utc>) -> Result<Bounds, UlidError> {
+        Ok(Bounds {
+            after: parse_bound(self.after.as_deref(), now)?,
+            before: parse_bound(self.before.as_deref(), now)?,
+        })
+    }
+
+    fn matches(&self, bounds: &Bounds, ulid: &Ulid, dt: &DateTime<FixedOffset>) -> bool {
+        self.year.is_none_or(|year| dt.year() == year)
+            && self.month.is_none_or(|month| dt.month() == month)
+            && self.day.is_none_or(|day| dt.day() == day)
+            && self.hour.is_none_or(|hour| dt.hour() == hour)
+            && self
+                .weekday
+                .is_none_or(|weekday| dt.weekday().num_days_from_monday() == weekday)
+            && bounds.after.is_none_or(|after| *dt > after)
+            && bounds.before.is_none_or(|before| *dt < before)
+            && self.lsb.is_none_or(|lsb| ulid.to_bytes()[15] & 0b1 == lsb)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AnalyticsRequest {
+    ulids: Vec<String>,
+    // iana name like `Europe/Paris` or fixed utc offset like `+01:00`, UTC by
+    // default
+    timezone: Option<String>,
+    group_by: Option<GroupBy>,
+    #[serde(default)]
+    predicates: BTreeMap<String, Predicate>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Analytics {
+    count: usize,
+    min: Option<String>,
+    max: Option<String>,
+    median: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<BTreeMap<String, usize>>,
+    predicates: BTreeMap<String, usize>,
+}
+
+enum Timezone {
+    // follows the zone's dst rules
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl Timezone {
+    // local time with the offset in effect at that instant
+    fn local(&self, dt: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Timezone::Named(tz) => dt.with_timezone(tz).fixed_offset(),
+            Timezone::Fixed(offset) => dt.with_timezone(offset),
+        }
+    }
+}
+
+fn parse_timezone(timezone: Option<&str>) -> Result<Timezone, UlidError> {
+    match timezone {
+        None | Some("UTC") | Some("Z") => Ok(Timezone::Fixed(Utc.fix())),
+        Some(name) => match name.parse::<Tz>() {
+            Ok(tz) => Ok(Timezone::Named(tz)),
+            Err(_) => name
+                .parse()
+                .map(Timezone::Fixed)
+                .map_err(|_| UlidError::InvalidTimezone(name.to_string())),
+        },
+    }
+}
+
+fn format_ms(ms: u64, timezone: &Timezone) -> Option<String> {
+    DateTime::from_timestamp_millis(ms as i64).map(|dt| {
+        timezone
+            .local(dt)
+            .to_rfc3339_opts(SecondsFormat::Millis, false)
+    })
+}
+
+pub async fn analytics(
+    Json(request): Json<AnalyticsRequest>,
+) -> Result<Json<Analytics>, UlidError> {
+    let timezone = parse_timezone(request.timezone.as_deref())?;
+    let ulids = parse_ulids(&request.ulids)?;
+    let now = Utc::now();
+    let predicates = request
+        .predicates
+        .iter()
+        .map(|(name, predicate)| Ok((name, predicate, predicate.bounds(now)?)))
+        .collect::<Result<Vec<_>, UlidError>>()?;
+
+    let mut groups = request.group_by.map(|_| BTreeMap::new());
+    let mut counts: BTreeMap<String, usize> = request
+        .predicates
+        .keys()
+        .map(|name| (name.clone(), 0))
+        .collect();
+    let mut timestamps = Vec::with_capacity(ulids.len());
+    for ulid in &ulids {
+        let ms = ulid.timestamp_ms();
+        timestamps.push(ms);
+        // ulid timestamps are 48 bits, always in chrono's range
+        let Some(dt) = DateTime::from_timestamp_millis(ms as i64) else {
+            continue;
+        };
+        let dt = timezone.local(dt);
+
+        if let (Some(groups), Some(group_by)) = (groups.as_mut(), request.group_by) {
+            *groups.entry(group_by.key(&dt)).or_insert(0) += 1;
+        }
+        for (name, predicate, bounds) in &predicates {
+            if let Some(count) = counts
+                .get_mut(name.as_str())
+                .filter(|_| predicate.matches(bounds, ulid, &dt))
+            {
+                *count += 1;
+            }
+        }
+    }
+
+    timestamps.sort_unstable();
+    let len = timestamps.len();
+    // lower middle for an even count, the value is always an actual timestamp
+    let median = (len > 0).then(|| timestamps[(len - 1) / 2]);
+    Ok(Json(Analytics {
+        count: len,
+        min: timestamps.first().and_then(|&ms| format_ms(ms, &timezone)),
+        max: timestamps.last().and_then(|&ms| format_ms(ms, &timezone)),
+        median: median.and_then(|ms| format_ms(ms, &timezone)),
+        groups,
+        predicates: counts,
+    }))
+}
@@ -0,0 +1,140 @@
+use std::time::{Duration, SystemTime};
+
+use axum::{
+    extract::{Path, Query},
+    Json,
+};
+use chrono::{DateTime, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use ulid::{Generator, Ulid};
+use uuid::Uuid;
+
+use super::UlidError;
+
+pub async fn uuids_to_ulids(
+    Json(uuids): Json<Vec<String>>,
+) -> Result<Json<Vec<String>>, UlidError> {
+    let ulids = uuids
+        .iter()
+        .enumerate()
+        .map(|(idx, uuid)| {
+            Uuid::parse_str(uuid)
+                .map(|uuid| Ulid::from(uuid).to_string())
+                .map_err(|e| UlidError::InvalidUuid(idx, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(ulids))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GenerateQuery {
+    count: Option<usize>,
+    // strictly increasing within the batch, even in the same millisecond
+    #[serde(default)]
+    monotonic: bool,
+    // rfc3339 or unix milliseconds, now by default
+    timestamp: Option<String>,
+}
+
+const MAX_GENERATE: usize = 1000;
+// ulid timestamps have 48 bits
+const MAX_TIMESTAMP_MS: u64 = (1 << 48) - 1;
+
+fn parse_timestamp(timestamp: &str) -> Result<SystemTime, UlidError> {
+    let invalid = || UlidError::InvalidTime(timestamp.to_string());
+    let ms = match timestamp.parse::<u64>() {
+        Ok(ms) => ms,
+        Err(_) => DateTime::parse_from_rfc3339(timestamp)
+            .map_err(|_| invalid())?
+            .timestamp_millis()
+            .try_into()
+            .map_err(|_| invalid())?,
+    };
+    if ms > MAX_TIMESTAMP_MS {
+        return Err(invalid());
+    }
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_millis(ms))
+}
+
+pub async fn generate(Query(query): Query<GenerateQuery>) -> Result<Json<Vec<String>>, UlidError> {
+    let count = query.count.unwrap_or(1);
+    if count > MAX_GENERATE {
+        return Err(UlidError::TooMany(MAX_GENERATE));
+    }
+    let datetime = query
+        .timestamp
+        .as_deref()
+        .map(parse_timestamp)
+        .transpose()?;
+
+    let mut generator = Generator::new();
+    let ulids = (0..count)
+        .map(|_| match (query.monotonic, datetime) {
+            (true, Some(datetime)) => generator.generate_from_datetime(datetime),
+            (true, None) => generator.generate(),
+            (false, Some(datetime)) => Ok(Ulid::from_datetime(datetime)),
+            (false, None) => Ok(Ulid::new()),
+        })
+        .map(|ulid| ulid.map(|ulid| ulid.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(ulids))
+}
+
+#[derive(Serialize, Debug)]
+pub struct Validation {
+    index: usize,
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ValidationReport {
+    valid: usize,
+    invalid: usize,
+    items: Vec<Validation>,
+}
+
+// never fails as a whole, every input gets its own verdict
+pub async fn validate(Json(ulids): Json<Vec<String>>) -> Json<ValidationReport> {
+    let items: Vec<Validation> = ulids
+        .iter()
+        .enumerate()
+        .map(|(index, ulid)| {
+            let error = Ulid::from_string(ulid).err().map(|e| e.to_string());
+            Validation {
+                index,
+                valid: error.is_none(),
+                error,
+            }
+        })
+        .collect();
+    let valid = items.iter().filter(|item| item.valid).count();
+    Json(ValidationReport {
+        valid,
+        invalid: items.len() - valid,
+        items,
+    })
+}
+
+#[derive(Serialize, Debug)]
+pub struct DecodedUlid {
+    ulid: String,
+    uuid: String,
+    timestamp_ms: u64,
+    datetime: Option<String>,
+    // the 80 random bits
+    randomness: String,
+}
+
+pub async fn decode(Path(ulid): Path<String>) -> Result<Json<DecodedUlid>, UlidError> {
+    let ulid = Ulid::from_string(&ulid).map_err(UlidError::Malformed)?;
+    Ok(Json(DecodedUlid {
+        ulid: ulid.to_string(),
+        uuid: Uuid::from(ulid).to_string(),
+        timestamp_ms: ulid.timestamp_ms(),
+        datetime: DateTime::from_timestamp_millis(ulid.timestamp_ms() as i64)
+            .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Millis, true)),
+        randomness: format!("{:020x}", ulid.random()),
+    }))
+}
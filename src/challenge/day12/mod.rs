@@ -0,0 +1,262 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+use shuttle_runtime::SecretStore;
+use stopwatch::{StopwatchOp, StopwatchReport, StopwatchState};
+use timekeeper::Timekeeper;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::prelude::*;
+use ulid::Ulid;
+use uuid::Uuid;
+
+pub mod analytics;
+pub mod convert;
+pub mod stopwatch;
+pub mod timekeeper;
+
+#[derive(Clone, Default, Debug)]
+pub struct TimekeeperConfig {
+    // saved packets are forgotten after this long, kept forever by default
+    pub ttl: Option<Duration>,
+}
+
+impl TimekeeperConfig {
+    pub fn from_secrets(secrets: &SecretStore) -> Self {
+        Self {
+            ttl: secrets
+                .get("PACKETS_TTL_SECS")
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_secs),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SharedState {
+    timekeeper: Arc<dyn Timekeeper>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Lsb {
+    #[serde(rename(serialize = "christmas eve"))]
+    eve: u8,
+    weekday: u8,
+    #[serde(rename(serialize = "in the future"))]
+    future: u8,
+    #[serde(rename(serialize = "LSB is 1"))]
+    lsb: u8,
+}
+
+pub fn routes(timekeeper: Arc<dyn Timekeeper>) -> Router {
+    let state = SharedState { timekeeper };
+    Router::new()
+        .route("/save/:packet_id", post(save))
+        .route("/load/:packet_id", get(load))
+        .route("/packets", get(list_packets))
+        .route("/packets/:packet_id", delete(delete_packet))
+        .route(
+            "/stopwatch/:packet_id",
+            get(stopwatch).delete(delete_stopwatch),
+        )
+        .route("/stopwatch/:packet_id/:op", post(stopwatch_op))
+        .route("/ulids", post(ulids_to_uuids))
+        .route("/ulids/:weekday", post(task3))
+        .route("/ulids/analytics", post(analytics::analytics))
+        .route("/ulids/generate", post(convert::generate))
+        .route("/ulids/validate", post(convert::validate))
+        .route("/ulids/decode/:ulid", get(convert::decode))
+        .route("/uuids", post(convert::uuids_to_ulids))
+        .with_state(state)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum UlidError {
+    #[error("invalid ulid at index {0}: {1}")]
+    InvalidUlid(usize, ulid::DecodeError),
+    // a single ulid, like a path parameter
+    #[error("invalid ulid: {0}")]
+    Malformed(ulid::DecodeError),
+    #[error("invalid uuid at index {0}: {1}")]
+    InvalidUuid(usize, uuid::Error),
+    #[error("at most {0} ulids per request")]
+    TooMany(usize),
+    #[error("cannot generate ulid: {0}")]
+    Monotonic(#[from] ulid::MonotonicError),
+    #[error("invalid timezone {0}, expected a zone like Europe/Paris or an offset like +01:00")]
+    InvalidTimezone(String),
+    #[error("invalid time: {0}")]
+    InvalidTime(String),
+}
+
+impl IntoResponse for UlidError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            // the random part overflowed within one millisecond
+            UlidError::Monotonic(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PacketError {
+    #[error("packet {0} not found")]
+    NotFound(String),
+    #[error("cannot {op} a {state} stopwatch")]
+    InvalidTransition {
+        op: StopwatchOp,
+        state: StopwatchState,
+    },
+    #[error("timekeeper failed: {0}")]
+    Store(#[from] anyhow::Error),
+    #[error("timekeeper failed: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for PacketError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            PacketError::NotFound(_) => StatusCode::NOT_FOUND,
+            PacketError::InvalidTransition { .. } => StatusCode::CONFLICT,
+            PacketError::Store(_) | PacketError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub fn parse_ulids(ulids: &[String]) -> Result<Vec<Ulid>, UlidError> {
+    ulids
+        .iter()
+        .enumerate()
+        .map(|(idx, ulid)| Ulid::from_string(ulid).map_err(|e| UlidError::InvalidUlid(idx, e)))
+        .collect()
+}
+
+pub async fn load(
+    Path(packet_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<String, PacketError> {
+    let elapsed = state
+        .timekeeper
+        .elapsed(packet_id.clone())
+        .await?
+        .ok_or(PacketError::NotFound(packet_id))?;
+    Ok(elapsed.as_secs().to_string())
+}
+
+pub async fn save(
+    Path(packet_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<(), PacketError> {
+    state.timekeeper.save(packet_id).await?;
+    Ok(())
+}
+
+#[derive(Serialize, Debug)]
+pub struct Packet {
+    id: String,
+    // whole seconds, as returned by `/load`
+    elapsed: u64,
+}
+
+pub async fn list_packets(
+    State(state): State<SharedState>,
+) -> Result<Json<Vec<Packet>>, PacketError> {
+    let packets = state
+        .timekeeper
+        .list()
+        .await?
+        .into_iter()
+        .map(|(id, elapsed)| Packet {
+            id,
+            elapsed: elapsed.as_secs(),
+        })
+        .collect();
+    Ok(Json(packets))
+}
+
+pub async fn delete_packet(
+    Path(packet_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<StatusCode, PacketError> {
+    if !state.timekeeper.remove(packet_id.clone()).await? {
+        return Err(PacketError::NotFound(packet_id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn stopwatch(
+    Path(packet_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<Json<StopwatchReport>, PacketError> {
+    Ok(Json(state.timekeeper.stopwatch(packet_id, None).await?))
+}
+
+pub async fn stopwatch_op(
+    Path((packet_id, op)): Path<(String, StopwatchOp)>,
+    State(state): State<SharedState>,
+) -> Result<Json<StopwatchReport>, PacketError> {
+    Ok(Json(state.timekeeper.stopwatch(packet_id, Some(op)).await?))
+}
+
+pub async fn delete_stopwatch(
+    Path(packet_id): Path<String>,
+    State(state): State<SharedState>,
+) -> Result<StatusCode, PacketError> {
+    if !state.timekeeper.remove_stopwatch(packet_id.clone()).await? {
+        return Err(PacketError::NotFound(packet_id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn ulids_to_uuids(
+    Json(ulids): Json<Vec<String>>,
+) -> Result<Json<Vec<String>>, UlidError> {
+    Ok(Json(
+        parse_ulids(&ulids)?
+            .into_iter()
+            .map(|ulid| Uuid::from(ulid).to_string())
+            .rev()
+            .collect::<Vec<String>>(),
+    ))
+}
+
+pub async fn task3(
+    Path(weekday): Path<u8>,
+    Json(ulids): Json<Vec<String>>,
+) -> Result<Json<Lsb>, UlidError> {
+    let mut lsb = Lsb::default();
+
+    for ulid in parse_ulids(&ulids)? {
+        let dt = DateTime::from_timestamp(ulid.timestamp_ms() as i64 / 1000, 0).unwrap();
+        if dt.month() == 12 && dt.day() == 24 {
+            lsb.eve += 1;
+        }
+        if dt.weekday().num_days_from_monday() as u8 == weekday {
+            lsb.weekday += 1;
+        }
+        if dt.timestamp()
+            > SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64
+        {
+            lsb.future += 1;
+        }
+        if ulid.to_bytes()[15] & 0b1 == 1 {
+            lsb.lsb += 1;
+        }
+    }
+    Ok(Json(lsb))
+}
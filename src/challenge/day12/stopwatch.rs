@@ -0,0 +1,157 @@
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::PacketError;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StopwatchState {
+    Running,
+    Paused,
+    Stopped,
+}
+
+impl fmt::Display for StopwatchState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StopwatchState::Running => "running",
+            StopwatchState::Paused => "paused",
+            StopwatchState::Stopped => "stopped",
+        })
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StopwatchOp {
+    // a stopped stopwatch can be started again from zero
+    Start,
+    Pause,
+    Resume,
+    Lap,
+    // records the remaining time as a last lap
+    Stop,
+}
+
+impl fmt::Display for StopwatchOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StopwatchOp::Start => "start",
+            StopwatchOp::Pause => "pause",
+            StopwatchOp::Resume => "resume",
+            StopwatchOp::Lap => "lap",
+            StopwatchOp::Stop => "stop",
+        })
+    }
+}
+
+// times are readings of the backend's clock, which only has to be
+// consistent for one stopwatch, a clock stepping back counts as no time
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Stopwatch {
+    state: StopwatchState,
+    // reading at the last start or resume
+    since: Duration,
+    // running time before `since`
+    accumulated: Duration,
+    // running time at each lap
+    laps: Vec<Duration>,
+}
+
+impl Stopwatch {
+    fn new(now: Duration) -> Self {
+        Self {
+            state: StopwatchState::Running,
+            since: now,
+            accumulated: Duration::ZERO,
+            laps: Vec::new(),
+        }
+    }
+
+    fn elapsed(&self, now: Duration) -> Duration {
+        match self.state {
+            StopwatchState::Running => self.accumulated + now.saturating_sub(self.since),
+            _ => self.accumulated,
+        }
+    }
+
+    pub fn report(&self, now: Duration) -> StopwatchReport {
+        let mut previous = Duration::ZERO;
+        let laps = self
+            .laps
+            .iter()
+            .enumerate()
+            .map(|(idx, &total)| {
+                let split = total.saturating_sub(previous);
+                previous = total;
+                LapReport {
+                    lap: idx + 1,
+                    split: split.as_secs_f64(),
+                    total: total.as_secs_f64(),
+                }
+            })
+            .collect();
+        StopwatchReport {
+            state: self.state,
+            elapsed: self.elapsed(now).as_secs_f64(),
+            laps,
+        }
+    }
+}
+
+// applies `op` to the packet's stopwatch, `None` if it has none yet
+pub fn step(
+    id: &str,
+    stopwatch: Option<Stopwatch>,
+    op: StopwatchOp,
+    now: Duration,
+) -> Result<Stopwatch, PacketError> {
+    let Some(mut stopwatch) = stopwatch else {
+        return match op {
+            StopwatchOp::Start => Ok(Stopwatch::new(now)),
+            _ => Err(PacketError::NotFound(id.to_string())),
+        };
+    };
+    match (op, stopwatch.state) {
+        (StopwatchOp::Start, StopwatchState::Stopped) => return Ok(Stopwatch::new(now)),
+        (StopwatchOp::Pause, StopwatchState::Running) => {
+            stopwatch.accumulated = stopwatch.elapsed(now);
+            stopwatch.state = StopwatchState::Paused;
+        }
+        (StopwatchOp::Resume, StopwatchState::Paused) => {
+            stopwatch.since = now;
+            stopwatch.state = StopwatchState::Running;
+        }
+        (StopwatchOp::Lap, StopwatchState::Running) => {
+            let total = stopwatch.elapsed(now);
+            stopwatch.laps.push(total);
+        }
+        (StopwatchOp::Stop, StopwatchState::Running | StopwatchState::Paused) => {
+            let total = stopwatch.elapsed(now);
+            if stopwatch.laps.last().is_none_or(|&last| total > last) {
+                stopwatch.laps.push(total);
+            }
+            stopwatch.accumulated = total;
+            stopwatch.state = StopwatchState::Stopped;
+        }
+        (op, state) => return Err(PacketError::InvalidTransition { op, state }),
+    }
+    Ok(stopwatch)
+}
+
+#[derive(Serialize, Debug)]
+pub struct LapReport {
+    lap: usize,
+    // seconds since the previous lap
+    split: f64,
+    // seconds since the start, pauses excluded
+    total: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StopwatchReport {
+    state: StopwatchState,
+    elapsed: f64,
+    laps: Vec<LapReport>,
+}
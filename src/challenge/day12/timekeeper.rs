@@ -0,0 +1,301 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use futures_util::future::BoxFuture;
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+
+use super::{
+    stopwatch::{step, Stopwatch, StopwatchOp, StopwatchReport},
+    PacketError,
+};
+use crate::db::{create_packets, create_stopwatches};
+
+// time since a packet was last saved, packets older than the ttl are gone
+pub trait Timekeeper: Send + Sync {
+    fn save(&self, id: String) -> BoxFuture<'_, anyhow::Result<()>>;
+    fn elapsed(&self, id: String) -> BoxFuture<'_, anyhow::Result<Option<Duration>>>;
+    // every live packet with its elapsed time, ordered by id
+    fn list(&self) -> BoxFuture<'_, anyhow::Result<Vec<(String, Duration)>>>;
+    fn remove(&self, id: String) -> BoxFuture<'_, anyhow::Result<bool>>;
+    // applies `op` to the packet's stopwatch, only reads it without one,
+    // stopwatches expire after the ttl since their last operation
+    fn stopwatch(
+        &self,
+        id: String,
+        op: Option<StopwatchOp>,
+    ) -> BoxFuture<'_, Result<StopwatchReport, PacketError>>;
+    fn remove_stopwatch(&self, id: String) -> BoxFuture<'_, anyhow::Result<bool>>;
+}
+
+// single instance, lost on restart, elapsed time from the monotonic clock
+pub struct MemoryTimekeeper {
+    packets: Mutex<HashMap<String, Instant>>,
+    // with the time of their last operation
+    stopwatches: Mutex<HashMap<String, (Stopwatch, Instant)>>,
+    // stopwatch clock readings are relative to this
+    epoch: Instant,
+    ttl: Option<Duration>,
+}
+
+impl MemoryTimekeeper {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            packets: Mutex::new(HashMap::new()),
+            stopwatches: Mutex::new(HashMap::new()),
+            epoch: Instant::now(),
+            ttl,
+        }
+    }
+
+    fn expired(&self, saved: &Instant) -> bool {
+        self.ttl.is_some_and(|ttl| saved.elapsed() > ttl)
+    }
+}
+
+impl Timekeeper for MemoryTimekeeper {
+    fn save(&self, id: String) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let mut packets = self.packets.lock().await;
+            // expired packets are dropped here so the map doesn't grow forever
+            packets.retain(|_, saved| !self.expired(saved));
+            packets.insert(id, Instant::now());
+            Ok(())
+        })
+    }
+
+    fn elapsed(&self, id: String) -> BoxFuture<'_, anyhow::Result<Option<Duration>>> {
+        Box::pin(async move {
+            let mut packets = self.packets.lock().await;
+            match packets.get(&id) {
+                Some(saved) if self.expired(saved) => {
+                    packets.remove(&id);
+                    Ok(None)
+                }
+                saved => Ok(saved.map(Instant::elapsed)),
+            }
+        })
+    }
+
+    fn list(&self) -> BoxFuture<'_, anyhow::Result<Vec<(String, Duration)>>> {
+        Box::pin(async move {
+            let mut packets = self.packets.lock().await;
+            packets.retain(|_, saved| !self.expired(saved));
+            let mut list: Vec<_> = packets
+                .iter()
+                .map(|(id, saved)| (id.clone(), saved.elapsed()))
+                .collect();
+            list.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+            Ok(list)
+        })
+    }
+
+    fn remove(&self, id: String) -> BoxFuture<'_, anyhow::Result<bool>> {
+        Box::pin(async move {
+            let removed = self.packets.lock().await.remove(&id);
+            Ok(removed.is_some_and(|saved| !self.expired(&saved)))
+        })
+    }
+
+    fn stopwatch(
+        &self,
+        id: String,
+        op: Option<StopwatchOp>,
+    ) -> BoxFuture<'_, Result<StopwatchReport, PacketError>> {
+        Box::pin(async move {
+            let now = self.epoch.elapsed();
+            let mut stopwatches = self.stopwatches.lock().await;
+            stopwatches.retain(|_, (_, updated)| !self.expired(updated));
+            let current = stopwatches.get(&id).map(|(stopwatch, _)| stopwatch.clone());
+            let stopwatch = match op {
+                None => current.ok_or_else(|| PacketError::NotFound(id.clone()))?,
+                Some(op) => {
+                    let stopwatch = step(&id, current, op, now)?;
+                    stopwatches.insert(id, (stopwatch.clone(), Instant::now()));
+                    stopwatch
+                }
+            };
+            Ok(stopwatch.report(now))
+        })
+    }
+
+    fn remove_stopwatch(&self, id: String) -> BoxFuture<'_, anyhow::Result<bool>> {
+        Box::pin(async move {
+            let removed = self.stopwatches.lock().await.remove(&id);
+            Ok(removed.is_some_and(|(_, updated)| !self.expired(&updated)))
+        })
+    }
+}
+
+// survives restarts and is shared between instances, elapsed time is taken
+// from the database clock so every instance agrees on it
+pub struct PgTimekeeper {
+    pool: PgPool,
+    ttl: Option<Duration>,
+}
+
+impl PgTimekeeper {
+    pub async fn new(pool: PgPool, ttl: Option<Duration>) -> anyhow::Result<Self> {
+        create_packets(&pool).await?;
+        create_stopwatches(&pool).await?;
+        if let Some(ttl) = ttl {
+            tokio::spawn(purge_expired(pool.clone(), ttl));
+        }
+        Ok(Self { pool, ttl })
+    }
+
+    fn ttl_secs(&self) -> Option<f64> {
+        self.ttl.map(|ttl| ttl.as_secs_f64())
+    }
+}
+
+// a clock stepping back yields zero instead of a negative interval
+const ELAPSED: &str = "EXTRACT(EPOCH FROM GREATEST(now() - saved_at, INTERVAL '0'))::FLOAT8";
+const LIVE: &str = "($1::FLOAT8 IS NULL OR saved_at > now() - make_interval(secs => $1))";
+const LIVE_STOPWATCH: &str =
+    "($1::FLOAT8 IS NULL OR updated_at > now() - make_interval(secs => $1))";
+// reads skip expired rows, they are deleted this often
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+async fn purge_expired(pool: PgPool, ttl: Duration) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        for statement in [
+            format!("DELETE FROM packets WHERE NOT {LIVE}"),
+            format!("DELETE FROM stopwatches WHERE NOT {LIVE_STOPWATCH}"),
+        ] {
+            if let Err(e) = sqlx::query(&statement)
+                .bind(ttl.as_secs_f64())
+                .execute(&pool)
+                .await
+            {
+                println!("Error purging expired packets and stopwatches: {}", e);
+            }
+        }
+    }
+}
+
+fn parse_stopwatch(state: &str) -> Result<Stopwatch, PacketError> {
+    Ok(serde_json::from_str(state).map_err(anyhow::Error::from)?)
+}
+
+impl Timekeeper for PgTimekeeper {
+    fn save(&self, id: String) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            sqlx::query(
+                "INSERT INTO packets (id, saved_at) VALUES ($1, now())
+                ON CONFLICT (id) DO UPDATE SET saved_at = EXCLUDED.saved_at",
+            )
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn elapsed(&self, id: String) -> BoxFuture<'_, anyhow::Result<Option<Duration>>> {
+        Box::pin(async move {
+            let elapsed: Option<f64> = sqlx::query_scalar(&format!(
+                "SELECT {ELAPSED} FROM packets WHERE {LIVE} AND id = $2"
+            ))
+            .bind(self.ttl_secs())
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+            Ok(elapsed.map(Duration::from_secs_f64))
+        })
+    }
+
+    fn list(&self) -> BoxFuture<'_, anyhow::Result<Vec<(String, Duration)>>> {
+        Box::pin(async move {
+            let rows: Vec<(String, f64)> = sqlx::query_as(&format!(
+                "SELECT id, {ELAPSED} FROM packets WHERE {LIVE} ORDER BY id"
+            ))
+            .bind(self.ttl_secs())
+            .fetch_all(&self.pool)
+            .await?;
+            Ok(rows
+                .into_iter()
+                .map(|(id, elapsed)| (id, Duration::from_secs_f64(elapsed)))
+                .collect())
+        })
+    }
+
+    fn remove(&self, id: String) -> BoxFuture<'_, anyhow::Result<bool>> {
+        Box::pin(async move {
+            let result = sqlx::query(&format!("DELETE FROM packets WHERE {LIVE} AND id = $2"))
+                .bind(self.ttl_secs())
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+            Ok(result.rows_affected() > 0)
+        })
+    }
+
+    fn stopwatch(
+        &self,
+        id: String,
+        op: Option<StopwatchOp>,
+    ) -> BoxFuture<'_, Result<StopwatchReport, PacketError>> {
+        Box::pin(async move {
+            let Some(op) = op else {
+                let (now, state): (f64, String) = sqlx::query_as(&format!(
+                    "SELECT EXTRACT(EPOCH FROM clock_timestamp())::FLOAT8, state::TEXT
+                    FROM stopwatches WHERE {LIVE_STOPWATCH} AND id = $2"
+                ))
+                .bind(self.ttl_secs())
+                .bind(&id)
+                .fetch_optional(&self.pool)
+                .await?
+                .ok_or_else(|| PacketError::NotFound(id.clone()))?;
+                let now = Duration::from_secs_f64(now.max(0.0));
+                return Ok(parse_stopwatch(&state)?.report(now));
+            };
+
+            let mut tx = self.pool.begin().await?;
+            let now: f64 =
+                sqlx::query_scalar("SELECT EXTRACT(EPOCH FROM clock_timestamp())::FLOAT8")
+                    .fetch_one(&mut *tx)
+                    .await?;
+            let now = Duration::from_secs_f64(now.max(0.0));
+            // locked until the new state is written
+            let current: Option<String> = sqlx::query_scalar(&format!(
+                "SELECT state::TEXT FROM stopwatches WHERE {LIVE_STOPWATCH} AND id = $2 FOR UPDATE"
+            ))
+            .bind(self.ttl_secs())
+            .bind(&id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let current = current.as_deref().map(parse_stopwatch).transpose()?;
+
+            let stopwatch = step(&id, current, op, now)?;
+            sqlx::query(
+                "INSERT INTO stopwatches (id, state, updated_at) VALUES ($1, $2::JSONB, now())
+                ON CONFLICT (id) DO UPDATE
+                SET state = EXCLUDED.state, updated_at = EXCLUDED.updated_at",
+            )
+            .bind(&id)
+            .bind(serde_json::to_string(&stopwatch).map_err(anyhow::Error::from)?)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            Ok(stopwatch.report(now))
+        })
+    }
+
+    fn remove_stopwatch(&self, id: String) -> BoxFuture<'_, anyhow::Result<bool>> {
+        Box::pin(async move {
+            let result = sqlx::query(&format!(
+                "DELETE FROM stopwatches WHERE {LIVE_STOPWATCH} AND id = $2"
+            ))
+            .bind(self.ttl_secs())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+            Ok(result.rows_affected() > 0)
+        })
+    }
+}
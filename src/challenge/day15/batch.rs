@@ -0,0 +1,166 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use shuttle_cch23::password::policy::Policy;
+
+use super::{naughty_reason, AppError, PasswordConfig};
+
+const NDJSON: &str = "application/x-ndjson";
+
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchRules {
+    // the conditions of `/nice`
+    #[default]
+    Nice,
+    // the password policy of `/game`
+    Game,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BatchQuery {
+    #[serde(default)]
+    rules: BatchRules,
+    // with `rules=game`, the default policy otherwise
+    policy: Option<String>,
+}
+
+// `"input"` or `{"input": "..."}` as sent to `/nice`
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum BatchItem {
+    Input(String),
+    Object { input: String },
+}
+
+#[derive(Serialize, Debug)]
+pub struct ItemResult {
+    index: usize,
+    input: String,
+    result: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct BatchSummary {
+    total: usize,
+    nice: usize,
+    naughty: usize,
+}
+
+impl BatchSummary {
+    fn add(&mut self, item: &ItemResult) {
+        self.total += 1;
+        match item.reason {
+            None => self.nice += 1,
+            Some(_) => self.naughty += 1,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchResult {
+    #[serde(flatten)]
+    summary: BatchSummary,
+    items: Vec<ItemResult>,
+}
+
+// a json array with a json content type, one input per line otherwise
+fn parse_items(headers: &HeaderMap, body: &Bytes) -> Result<Vec<String>, AppError> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if is_json {
+        let items: Vec<BatchItem> =
+            serde_json::from_slice(body).map_err(|e| AppError::InvalidBatch(e.to_string()))?;
+        return Ok(items
+            .into_iter()
+            .map(|item| match item {
+                BatchItem::Input(input) | BatchItem::Object { input } => input,
+            })
+            .collect());
+    }
+    let text = std::str::from_utf8(body).map_err(|e| AppError::InvalidBatch(e.to_string()))?;
+    Ok(text
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn classify(rules: BatchRules, policy: &Policy, index: usize, input: String) -> ItemResult {
+    let reason = match rules {
+        BatchRules::Nice => naughty_reason(&input).map(str::to_string),
+        BatchRules::Game => policy.check(&input).err().map(|e| e.to_string()),
+    };
+    ItemResult {
+        index,
+        input,
+        result: if reason.is_none() { "nice" } else { "naughty" },
+        reason,
+    }
+}
+
+fn json_line<T: Serialize>(value: &T) -> Bytes {
+    let mut line = serde_json::to_vec(value).unwrap();
+    line.push(b'\n');
+    line.into()
+}
+
+// with `Accept: application/x-ndjson` every item is classified while the
+// response is written, one result per line and the summary last
+pub async fn batch(
+    State(config): State<Arc<PasswordConfig>>,
+    Query(query): Query<BatchQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let items = parse_items(&headers, &body)?;
+    let policy = match query.rules {
+        BatchRules::Nice => Policy::default(),
+        BatchRules::Game => config.policy(query.policy.as_deref())?.clone(),
+    };
+
+    let streaming = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains(NDJSON));
+    if !streaming {
+        let mut summary = BatchSummary::default();
+        let items: Vec<ItemResult> = items
+            .into_iter()
+            .enumerate()
+            .map(|(index, input)| classify(query.rules, &policy, index, input))
+            .inspect(|item| summary.add(item))
+            .collect();
+        return Ok(Json(BatchResult { summary, items }).into_response());
+    }
+
+    let mut items = items.into_iter().enumerate();
+    let mut summary = Some(BatchSummary::default());
+    let lines = std::iter::from_fn(move || match items.next() {
+        Some((index, input)) => {
+            let item = classify(query.rules, &policy, index, input);
+            if let Some(summary) = summary.as_mut() {
+                summary.add(&item);
+            }
+            Some(json_line(&item))
+        }
+        None => summary.take().map(|summary| json_line(&summary)),
+    });
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static(NDJSON))],
+        Body::from_stream(stream::iter(lines.map(Ok::<_, Infallible>))),
+    )
+        .into_response())
+}
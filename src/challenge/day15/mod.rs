@@ -0,0 +1,270 @@
+use std::{collections::HashMap, path::Path as FsPath, sync::Arc};
+
+use axum::{
+    extract::{rejection::JsonRejection, DefaultBodyLimit, FromRequest, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shuttle_cch23::password::{
+    policy::{load_policies, Policy, DEFAULT_POLICY},
+    score, RuleError,
+};
+use shuttle_runtime::SecretStore;
+
+use crate::util::parse_secret;
+
+pub mod batch;
+
+// https://github.com/tokio-rs/axum/blob/main/examples/error-handling/src/main.rs
+
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct AppJson<T>(T);
+
+impl<T> IntoResponse for AppJson<T>
+where
+    Json<T>: IntoResponse,
+{
+    fn into_response(self) -> Response {
+        Json(self.0).into_response()
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AppError {
+    #[error("Json error: {0}")]
+    JsonRejection(#[from] JsonRejection),
+    #[error(transparent)]
+    Rule(#[from] RuleError),
+    #[error("unknown policy {0}")]
+    UnknownPolicy(String),
+    #[error("invalid batch: {0}")]
+    InvalidBatch(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::JsonRejection(rejection) => rejection.status(),
+            AppError::Rule(e) => e.status(),
+            AppError::UnknownPolicy(_) => StatusCode::NOT_FOUND,
+            AppError::InvalidBatch(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let reason = match self {
+            AppError::JsonRejection(rejection) => rejection.body_text(),
+            _ => self.to_string(),
+        };
+        (
+            status,
+            AppJson(AppResponse {
+                result: "naughty".to_string(),
+                reason,
+            }),
+        )
+            .into_response()
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AppResponse {
+    result: String,
+    reason: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct PasswordConfig {
+    pub policies: HashMap<String, Policy>,
+    // used by `/game` unless `?policy=` picks another one
+    pub default_policy: String,
+    // request body of `/nice/batch`
+    pub max_batch_size: usize,
+}
+
+impl PasswordConfig {
+    pub fn from_secrets(secrets: &SecretStore) -> anyhow::Result<Self> {
+        let dir = secrets.get("PASSWORD_POLICY_DIR");
+        let policies = load_policies(dir.as_deref().map(FsPath::new))?;
+        let default_policy = secrets
+            .get("PASSWORD_POLICY")
+            .unwrap_or(DEFAULT_POLICY.to_string());
+        // a typo would otherwise fail every request instead of the startup
+        if !policies.contains_key(&default_policy) {
+            let mut names: Vec<&str> = policies.keys().map(String::as_str).collect();
+            names.sort();
+            anyhow::bail!(
+                "unknown PASSWORD_POLICY {}, expected one of {}",
+                default_policy,
+                names.join(", ")
+            );
+        }
+        Ok(Self {
+            policies,
+            default_policy,
+            max_batch_size: parse_secret(
+                secrets,
+                "PASSWORD_MAX_BATCH_SIZE",
+                DEFAULT_MAX_BATCH_SIZE,
+            )?,
+        })
+    }
+
+    fn policy(&self, name: Option<&str>) -> Result<&Policy, AppError> {
+        let name = name.unwrap_or(&self.default_policy);
+        self.policies
+            .get(name)
+            .ok_or_else(|| AppError::UnknownPolicy(name.to_string()))
+    }
+}
+
+const DEFAULT_MAX_BATCH_SIZE: usize = 16 * 1024 * 1024;
+
+pub fn routes(config: PasswordConfig) -> Router {
+    Router::new()
+        .route("/nice", post(task1))
+        .route(
+            "/nice/batch",
+            post(batch::batch).layer(DefaultBodyLimit::max(config.max_batch_size)),
+        )
+        .route("/game", post(task2))
+        .route("/game/:policy", post(task2_policy))
+        .with_state(Arc::new(config))
+}
+
+// the first of the `/nice` conditions `s` fails
+fn naughty_reason(s: &str) -> Option<&'static str> {
+    let cond1 = s.chars().filter(|c| "aeiouy".contains(*c)).count() >= 3;
+    let cond2 = s
+        .chars()
+        .zip(s.chars().skip(1))
+        .any(|(a, b)| a == b && a.is_alphabetic());
+    let cond3 = !s.contains("ab") && !s.contains("cd") && !s.contains("pq") && !s.contains("xy");
+
+    match (cond1, cond2, cond3) {
+        (false, _, _) => Some("not enough vowels"),
+        (_, false, _) => Some("no double letter"),
+        (_, _, false) => Some("forbidden substring"),
+        _ => None,
+    }
+}
+
+fn validate(s: &str) -> bool {
+    naughty_reason(s).is_none()
+}
+
+pub async fn task1(Json(payload): Json<Value>) -> (StatusCode, Json<Value>) {
+    if validate(payload["input"].as_str().unwrap_or("")) {
+        (StatusCode::OK, Json(json!({"result": "nice"})))
+    } else {
+        (StatusCode::BAD_REQUEST, Json(json!({"result": "naughty"})))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GameQuery {
+    policy: Option<String>,
+    #[serde(default)]
+    mode: GameMode,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    // the first failing rule is the answer
+    #[default]
+    First,
+    // every rule is checked and reported
+    All,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Violation {
+    rule: &'static str,
+    reason: String,
+    status: u16,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GameReport {
+    result: String,
+    // see `password::score`
+    score: u8,
+    violations: Vec<Violation>,
+}
+
+fn report(policy: &Policy, content: &str) -> (StatusCode, GameReport) {
+    let violations = policy.violations(content);
+    let score = score(policy.rules.len(), violations.len(), content);
+
+    // the status of the first failure, as without the report
+    let status = violations
+        .first()
+        .map(|(_, e)| e.status())
+        .unwrap_or(StatusCode::OK);
+    let violations: Vec<Violation> = violations
+        .into_iter()
+        .map(|(rule, e)| Violation {
+            rule: rule.rule.name(),
+            reason: e.to_string(),
+            status: e.status().as_u16(),
+        })
+        .collect();
+    let result = if violations.is_empty() {
+        "nice"
+    } else {
+        "naughty"
+    };
+    (
+        status,
+        GameReport {
+            result: result.to_string(),
+            score,
+            violations,
+        },
+    )
+}
+
+fn play(policy: &Policy, mode: GameMode, payload: &Value) -> Result<Response, AppError> {
+    let content = payload["input"].as_str().unwrap_or("");
+    if let GameMode::All = mode {
+        let (status, report) = report(policy, content);
+        return Ok((status, AppJson(report)).into_response());
+    }
+    policy.check(content)?;
+    Ok(AppJson(AppResponse {
+        result: "nice".to_string(),
+        reason: "that's a nice password".to_string(),
+    })
+    .into_response())
+}
+
+pub async fn task2(
+    State(config): State<Arc<PasswordConfig>>,
+    Query(query): Query<GameQuery>,
+    Json(payload): Json<Value>,
+) -> Result<Response, AppError> {
+    play(
+        config.policy(query.policy.as_deref())?,
+        query.mode,
+        &payload,
+    )
+}
+
+// the policy of the path wins over `?policy=`
+pub async fn task2_policy(
+    State(config): State<Arc<PasswordConfig>>,
+    Path(policy): Path<String>,
+    Query(query): Query<GameQuery>,
+    Json(payload): Json<Value>,
+) -> Result<Response, AppError> {
+    play(config.policy(Some(&policy))?, query.mode, &payload)
+}
@@ -0,0 +1,97 @@
+use std::time::{Duration, SystemTime};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::util::constant_time_eq;
+
+// application close codes sent to rejected chat clients
+pub const CLOSE_UNAUTHORIZED: u16 = 4001;
+pub const CLOSE_USERNAME_TAKEN: u16 = 4009;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("missing token")]
+    Missing,
+    #[error("malformed token")]
+    Malformed,
+    #[error("invalid token signature")]
+    BadSignature,
+    #[error("token expired")]
+    Expired,
+    #[error("token issued for another user")]
+    WrongUser,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Claims {
+    pub user: String,
+    // unix seconds
+    pub exp: u64,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc2104
+fn hmac_sha256(key: &[u8], msg: &[u8]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let ipad: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    let opad: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+
+    let inner = Sha256::new()
+        .chain_update(ipad)
+        .chain_update(msg)
+        .finalize();
+    Sha256::new()
+        .chain_update(opad)
+        .chain_update(inner)
+        .finalize()
+        .to_vec()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// <base64url claims>.<base64url hmac of the encoded claims>
+pub fn issue_token(secret: &[u8], user: &str, ttl: Duration) -> (String, Claims) {
+    let claims = Claims {
+        user: user.to_string(),
+        exp: now() + ttl.as_secs(),
+    };
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap());
+    let signature = URL_SAFE_NO_PAD.encode(hmac_sha256(secret, payload.as_bytes()));
+    (format!("{}.{}", payload, signature), claims)
+}
+
+pub fn verify_token(secret: &[u8], token: &str, user: &str) -> Result<Claims, AuthError> {
+    let (payload, signature) = token.split_once('.').ok_or(AuthError::Malformed)?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| AuthError::Malformed)?;
+    if !constant_time_eq(&signature, &hmac_sha256(secret, payload.as_bytes())) {
+        return Err(AuthError::BadSignature);
+    }
+
+    let claims: Claims = URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()
+        .and_then(|payload| serde_json::from_slice(&payload).ok())
+        .ok_or(AuthError::Malformed)?;
+    if claims.exp <= now() {
+        return Err(AuthError::Expired);
+    }
+    if claims.user != user {
+        return Err(AuthError::WrongUser);
+    }
+    Ok(claims)
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use axum::extract::ws::{Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+const MAX_ROUNDS: u32 = 100;
+// a pong later than this scores nothing
+const ROUND_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+pub struct GameQuery {
+    rounds: Option<u32>,
+}
+
+impl GameQuery {
+    pub fn rounds(&self, default: u32) -> u32 {
+        self.rounds.unwrap_or(default).clamp(1, MAX_ROUNDS)
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Score {
+    player: String,
+    best: u64,
+    games: u32,
+}
+
+#[derive(Default)]
+pub struct Leaderboard {
+    scores: Mutex<HashMap<String, Score>>,
+}
+
+impl Leaderboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn record(&self, player: &str, score: u64) {
+        let mut scores = self.scores.lock().await;
+        let entry = scores.entry(player.to_string()).or_insert_with(|| Score {
+            player: player.to_string(),
+            best: 0,
+            games: 0,
+        });
+        entry.best = entry.best.max(score);
+        entry.games += 1;
+    }
+
+    pub async fn top(&self, limit: usize) -> Vec<Score> {
+        let mut scores: Vec<Score> = self.scores.lock().await.values().cloned().collect();
+        scores.sort_by(|a, b| b.best.cmp(&a.best).then_with(|| a.player.cmp(&b.player)));
+        scores.truncate(limit);
+        scores
+    }
+}
+
+// 1000 points for an instant pong, nothing after ROUND_TIMEOUT
+fn points(latency: Duration) -> u64 {
+    let timeout = ROUND_TIMEOUT.as_millis() as u64;
+    let latency = (latency.as_millis() as u64).min(timeout);
+    (timeout - latency) * 1000 / timeout
+}
+
+async fn send_json(socket: &mut WebSocket, value: serde_json::Value) -> bool {
+    socket.send(Message::from(value.to_string())).await.is_ok()
+}
+
+// waits for the next "pong", skipping late answers to rounds that already
+// timed out, None when the socket is gone
+async fn wait_pong(socket: &mut WebSocket, stale: &mut u32) -> Option<()> {
+    while let Some(Ok(msg)) = socket.recv().await {
+        if msg.to_text().unwrap_or("") != "pong" {
+            continue;
+        }
+        if *stale == 0 {
+            return Some(());
+        }
+        *stale -= 1;
+    }
+    None
+}
+
+// "serve" starts a game of `rounds` pings, each answered "pong" scores by latency
+pub async fn play(mut socket: WebSocket, player: String, rounds: u32, leaderboard: &Leaderboard) {
+    while let Some(Ok(msg)) = socket.recv().await {
+        if msg.to_text().unwrap_or("") != "serve" {
+            continue;
+        }
+
+        let mut score = 0;
+        let mut stale = 0;
+        for round in 1..=rounds {
+            if !send_json(&mut socket, json!({ "type": "ping", "round": round })).await {
+                return;
+            }
+            let start = Instant::now();
+            let pong = wait_pong(&mut socket, &mut stale);
+            let points = match tokio::time::timeout(ROUND_TIMEOUT, pong).await {
+                Ok(Some(())) => points(start.elapsed()),
+                Ok(None) => return,
+                Err(_) => {
+                    stale += 1;
+                    0
+                }
+            };
+            score += points;
+            let frame = json!({
+                "type": "round",
+                "round": round,
+                "latency_ms": start.elapsed().as_millis() as u64,
+                "points": points,
+            });
+            if !send_json(&mut socket, frame).await {
+                return;
+            }
+        }
+
+        leaderboard.record(&player, score).await;
+        let frame = json!({ "type": "score", "player": player, "rounds": rounds, "score": score });
+        if !send_json(&mut socket, frame).await {
+            return;
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use serde::Serialize;
+
+// counters are only touched through atomics, the maps are locked when a
+// connection is opened or closed or the stats are read, never per message
+#[derive(Default)]
+pub struct Counters {
+    sent: AtomicU64,
+    delivered: AtomicU64,
+    lagged: AtomicU64,
+    dropped: AtomicU64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CountersSnapshot {
+    sent: u64,
+    delivered: u64,
+    lagged: u64,
+    dropped: u64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> CountersSnapshot {
+        CountersSnapshot {
+            sent: self.sent.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            lagged: self.lagged.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    fn clear(&self) {
+        self.sent.store(0, Ordering::Relaxed);
+        self.delivered.store(0, Ordering::Relaxed);
+        self.lagged.store(0, Ordering::Relaxed);
+        self.dropped.store(0, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+pub struct RoomMetrics {
+    counters: Counters,
+    users: RwLock<HashMap<String, Arc<Counters>>>,
+}
+
+#[derive(Default)]
+pub struct ChatMetrics {
+    total: Counters,
+    rooms: RwLock<HashMap<u32, Arc<RoomMetrics>>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RoomSnapshot {
+    #[serde(flatten)]
+    counters: CountersSnapshot,
+    users: BTreeMap<String, CountersSnapshot>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChatStats {
+    total: CountersSnapshot,
+    rooms: BTreeMap<u32, RoomSnapshot>,
+}
+
+impl ChatMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connection(self: &Arc<Self>, room_number: u32, username: &str) -> ConnectionMetrics {
+        let room = self
+            .rooms
+            .write()
+            .unwrap()
+            .entry(room_number)
+            .or_default()
+            .clone();
+        let user = room
+            .users
+            .write()
+            .unwrap()
+            .entry(username.to_string())
+            .or_default()
+            .clone();
+        ConnectionMetrics {
+            chat: self.clone(),
+            room,
+            user,
+            room_number,
+            username: username.to_string(),
+        }
+    }
+
+    pub fn stats(&self) -> ChatStats {
+        let rooms = self
+            .rooms
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(room_number, room)| {
+                let users = room
+                    .users
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(username, user)| (username.clone(), user.snapshot()))
+                    .collect();
+                let snapshot = RoomSnapshot {
+                    counters: room.counters.snapshot(),
+                    users,
+                };
+                (*room_number, snapshot)
+            })
+            .collect();
+        ChatStats {
+            total: self.total.snapshot(),
+            rooms,
+        }
+    }
+
+    // zero in place so open connections keep counting into the same entries
+    pub fn reset(&self) {
+        self.total.clear();
+        for room in self.rooms.read().unwrap().values() {
+            room.counters.clear();
+            for user in room.users.read().unwrap().values() {
+                user.clear();
+            }
+        }
+    }
+}
+
+// counters of a single user in a single room, rolled up into room and total;
+// the user and room entries are removed with their last connection
+pub struct ConnectionMetrics {
+    chat: Arc<ChatMetrics>,
+    room: Arc<RoomMetrics>,
+    user: Arc<Counters>,
+    room_number: u32,
+    username: String,
+}
+
+impl ConnectionMetrics {
+    fn each(&self, f: impl Fn(&Counters)) {
+        f(&self.chat.total);
+        f(&self.room.counters);
+        f(&self.user);
+    }
+
+    pub fn sent(&self) {
+        self.each(|c| {
+            c.sent.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    pub fn delivered(&self) {
+        self.each(|c| {
+            c.delivered.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    pub fn dropped(&self, dropped: u64) {
+        self.each(|c| {
+            c.dropped.fetch_add(dropped, Ordering::Relaxed);
+        });
+    }
+
+    pub fn lagged(&self, skipped: u64) {
+        self.each(|c| {
+            c.lagged.fetch_add(1, Ordering::Relaxed);
+            c.dropped.fetch_add(skipped, Ordering::Relaxed);
+        });
+    }
+}
+
+impl Drop for ConnectionMetrics {
+    // entries are only cloned out of the maps under their write lock, so a
+    // count of two (map and self) means no other connection holds them
+    fn drop(&mut self) {
+        let mut users = self.room.users.write().unwrap();
+        if Arc::strong_count(&self.user) == 2 {
+            users.remove(&self.username);
+        }
+        drop(users);
+
+        let mut rooms = self.chat.rooms.write().unwrap();
+        if Arc::strong_count(&self.room) == 2 && self.room.users.read().unwrap().is_empty() {
+            rooms.remove(&self.room_number);
+        }
+    }
+}
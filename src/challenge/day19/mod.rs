@@ -0,0 +1,607 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        Path, Query, State, WebSocketUpgrade,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    routing::{get, post},
+    Json, Router,
+};
+use futures_util::{
+    stream::{SplitSink, SplitStream, StreamExt},
+    SinkExt,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use shuttle_runtime::SecretStore;
+use tokio::sync::{
+    broadcast::{error::RecvError, Receiver, Sender},
+    Mutex,
+};
+
+use crate::{
+    pubsub::PubSub,
+    util::{constant_time_eq, parse_secret},
+};
+
+use auth::{AuthError, CLOSE_UNAUTHORIZED, CLOSE_USERNAME_TAKEN};
+use game::{GameQuery, Leaderboard, Score};
+use metrics::{ChatMetrics, ChatStats, ConnectionMetrics};
+use outbound::{Frame, Outbound, SlowConsumerPolicy};
+
+pub mod auth;
+pub mod game;
+pub mod metrics;
+pub mod outbound;
+
+#[derive(Clone, Debug)]
+pub struct ChatConfig {
+    // messages buffered per room before a receiver lags
+    pub capacity: usize,
+    // frames buffered per connection before the slow consumer policy kicks in
+    pub queue_size: usize,
+    pub slow_consumer: SlowConsumerPolicy,
+    pub heartbeat: Duration,
+    pub idle_timeout: Duration,
+    // when set, joining a room requires a token issued by /login
+    pub secret: Option<Vec<u8>>,
+    // password /login asks for before issuing a token, set with `secret`
+    pub login_secret: Option<Vec<u8>>,
+    pub token_ttl: Duration,
+    // pings per game when the client doesn't ask for a number of rounds
+    pub game_rounds: u32,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 100000,
+            queue_size: 1024,
+            slow_consumer: SlowConsumerPolicy::Disconnect,
+            heartbeat: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(90),
+            secret: None,
+            login_secret: None,
+            token_ttl: Duration::from_secs(3600),
+            game_rounds: 5,
+        }
+    }
+}
+
+// broadcast channels and outbound queues grow up to these
+const MAX_CAPACITY: u64 = 1 << 24;
+// keeps timer deadlines far from overflowing
+const MAX_SECS: u64 = 365 * 24 * 3600;
+
+impl ChatConfig {
+    pub fn from_secrets(secrets: &SecretStore) -> anyhow::Result<Self> {
+        let default = Self::default();
+        // zero would panic the channel or timer, or drop every client
+        let parse_in = |key: &str, default: u64, max: u64| {
+            let value = parse_secret(secrets, key, default)?;
+            if !(1..=max).contains(&value) {
+                anyhow::bail!("{} must be between 1 and {}, got {}", key, max, value);
+            }
+            Ok(value)
+        };
+        // an empty key would let anyone sign tokens or log in
+        let non_empty = |key: &str| -> anyhow::Result<Option<Vec<u8>>> {
+            match secrets.get(key) {
+                Some(value) if value.trim().is_empty() => {
+                    anyhow::bail!("{} must not be empty", key)
+                }
+                value => Ok(value.map(String::into_bytes)),
+            }
+        };
+        let secret = non_empty("CHAT_SECRET")?;
+        let login_secret = non_empty("CHAT_LOGIN_SECRET")?;
+        if secret.is_some() && login_secret.is_none() {
+            anyhow::bail!("CHAT_LOGIN_SECRET is required with CHAT_SECRET");
+        }
+        Ok(Self {
+            capacity: parse_in("CHAT_CAPACITY", default.capacity as u64, MAX_CAPACITY)? as usize,
+            queue_size: parse_in("CHAT_QUEUE_SIZE", default.queue_size as u64, MAX_CAPACITY)?
+                as usize,
+            slow_consumer: parse_secret(secrets, "CHAT_SLOW_CONSUMER", default.slow_consumer)?,
+            heartbeat: Duration::from_secs(parse_in(
+                "CHAT_HEARTBEAT_SECS",
+                default.heartbeat.as_secs(),
+                MAX_SECS,
+            )?),
+            idle_timeout: Duration::from_secs(parse_in(
+                "CHAT_IDLE_TIMEOUT_SECS",
+                default.idle_timeout.as_secs(),
+                MAX_SECS,
+            )?),
+            secret,
+            login_secret,
+            token_ttl: Duration::from_secs(parse_in(
+                "CHAT_TOKEN_TTL_SECS",
+                default.token_ttl.as_secs(),
+                MAX_SECS,
+            )?),
+            game_rounds: parse_secret(secrets, "CHAT_GAME_ROUNDS", default.game_rounds)?,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct ChatState {
+    rooms: Arc<Mutex<HashMap<u32, RoomState>>>,
+    pubsub: Arc<dyn PubSub>,
+    metrics: Arc<ChatMetrics>,
+    leaderboard: Arc<Leaderboard>,
+    config: ChatConfig,
+}
+
+impl ChatState {
+    pub fn new(pubsub: Arc<dyn PubSub>, config: ChatConfig) -> Self {
+        Self {
+            rooms: Arc::new(Mutex::new(HashMap::new())),
+            pubsub,
+            metrics: Arc::new(ChatMetrics::new()),
+            leaderboard: Arc::new(Leaderboard::new()),
+            config,
+        }
+    }
+}
+
+// users connected to this instance
+pub struct RoomState {
+    users: Mutex<HashSet<String>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChatMsg {
+    message: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TweetMsg {
+    user: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    username: String,
+    // CHAT_LOGIN_SECRET
+    #[serde(default)]
+    password: String,
+}
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    token: String,
+    expires_at: u64,
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: Option<String>,
+}
+
+pub fn routes(pubsub: Arc<dyn PubSub>, config: ChatConfig) -> Router {
+    Router::new()
+        .route("/ws/ping", get(serve))
+        .route("/ws/game/:player", get(serve_game))
+        .route("/game/leaderboard", get(leaderboard))
+        .route("/reset", post(reset))
+        .route("/views", get(views))
+        .route("/stats", get(stats))
+        .route("/login", post(login))
+        .route("/ws/room/:room_number/user/:username", get(serve_chat))
+        .with_state(Arc::new(ChatState::new(pubsub, config)))
+}
+
+pub async fn serve(ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(ping)
+}
+
+async fn ping(mut socket: WebSocket) {
+    let mut started = false;
+    while let Some(msg) = socket.recv().await {
+        let msg = if let Ok(msg) = msg {
+            msg
+        } else {
+            break;
+        };
+        let msg = msg.to_text().unwrap();
+        if msg == "serve" {
+            started = true;
+        }
+        if !started {
+            continue;
+        }
+        if msg == "ping" {
+            socket
+                .send(Message::from("pong"))
+                .await
+                .expect("Failed to send message to client");
+        }
+    }
+}
+
+pub async fn serve_game(
+    ws: WebSocketUpgrade,
+    Path(player): Path<String>,
+    Query(query): Query<GameQuery>,
+    State(state): State<Arc<ChatState>>,
+) -> Response {
+    let rounds = query.rounds(state.config.game_rounds);
+    ws.on_upgrade(move |socket| async move {
+        game::play(socket, player, rounds, &state.leaderboard).await
+    })
+}
+
+#[derive(Deserialize)]
+pub struct LeaderboardQuery {
+    limit: Option<usize>,
+}
+
+pub async fn leaderboard(
+    Query(query): Query<LeaderboardQuery>,
+    State(state): State<Arc<ChatState>>,
+) -> Json<Vec<Score>> {
+    Json(state.leaderboard.top(query.limit.unwrap_or(10)).await)
+}
+
+pub async fn reset(State(state): State<Arc<ChatState>>) -> Result<(), StatusCode> {
+    state
+        .pubsub
+        .reset()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    (*state.rooms.lock().await).clear();
+    state.metrics.reset();
+    Ok(())
+}
+
+pub async fn views(State(state): State<Arc<ChatState>>) -> Result<String, StatusCode> {
+    let views = state
+        .pubsub
+        .views()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(views.to_string())
+}
+
+pub async fn stats(State(state): State<Arc<ChatState>>) -> Json<ChatStats> {
+    Json(state.metrics.stats())
+}
+
+pub async fn login(
+    State(state): State<Arc<ChatState>>,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, StatusCode> {
+    let (Some(secret), Some(login_secret)) = (&state.config.secret, &state.config.login_secret)
+    else {
+        return Err(StatusCode::NOT_IMPLEMENTED);
+    };
+    if payload.username.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !constant_time_eq(payload.password.as_bytes(), login_secret) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let (token, claims) = auth::issue_token(secret, &payload.username, state.config.token_ttl);
+    Ok(Json(LoginResponse {
+        token,
+        expires_at: claims.exp,
+    }))
+}
+
+// browsers can't set headers on a websocket handshake, so the query is accepted too
+fn authenticate(
+    state: &ChatState,
+    username: &str,
+    query: TokenQuery,
+    headers: &HeaderMap,
+) -> Result<(), AuthError> {
+    let Some(secret) = &state.config.secret else {
+        return Ok(());
+    };
+    let token = query
+        .token
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(str::to_string)
+        })
+        .ok_or(AuthError::Missing)?;
+    auth::verify_token(secret, &token, username)?;
+    Ok(())
+}
+
+async fn reject(mut socket: WebSocket, code: u16, reason: String) {
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code,
+            reason: Cow::from(reason),
+        })))
+        .await;
+}
+
+pub async fn serve_chat(
+    ws: WebSocketUpgrade,
+    Path((room_number, username)): Path<(u32, String)>,
+    Query(query): Query<TokenQuery>,
+    headers: HeaderMap,
+    State(state): State<Arc<ChatState>>,
+) -> Response {
+    if let Err(e) = authenticate(&state, &username, query, &headers) {
+        println!("{} rejected from room {}: {}", username, room_number, e);
+        return ws.on_upgrade(move |socket| reject(socket, CLOSE_UNAUTHORIZED, e.to_string()));
+    }
+    ws.on_upgrade(move |socket| handle_socket(socket, room_number, username, state))
+}
+
+pub async fn handle_socket(
+    socket: WebSocket,
+    room_number: u32,
+    username: String,
+    state: Arc<ChatState>,
+) {
+    // join room
+    if !join_room(room_number, username.clone(), state.rooms.clone()).await {
+        println!("{} failed to join room {}", username, room_number);
+        let reason = "username already connected".to_string();
+        reject(socket, CLOSE_USERNAME_TAKEN, reason).await;
+        return;
+    }
+
+    let (sender, receiver) = socket.split();
+
+    println!("{} joined room {}", username, room_number);
+
+    let (tx, rx) = state.pubsub.subscribe(room_number).await;
+    let metrics = Arc::new(state.metrics.connection(room_number, &username));
+    let outbound = Arc::new(Outbound::new(
+        state.config.queue_size,
+        state.config.slow_consumer,
+    ));
+    let last_seen = Arc::new(std::sync::Mutex::new(Instant::now()));
+
+    // receive broadcasted messages and queue them for the socket
+    let mut handle1 = tokio::spawn(broadcast_chat_message(
+        outbound.clone(),
+        username.clone(),
+        rx,
+        state.config.slow_consumer,
+        metrics.clone(),
+    ));
+    // receive socket and send broadcast messages
+    let mut handle2 = tokio::spawn(send_chat_messages(
+        receiver,
+        room_number,
+        tx,
+        state.pubsub.clone(),
+        username.clone(),
+        metrics.clone(),
+        last_seen.clone(),
+    ));
+    // drain the queue into the socket, ping and reap idle clients, increment total tweets
+    let mut handle3 = tokio::spawn(write_chat_frames(
+        sender,
+        outbound,
+        username.clone(),
+        state.config.clone(),
+        state.pubsub.clone(),
+        metrics,
+        last_seen,
+    ));
+    tokio::select! {
+        _ = &mut handle1 => {
+            // let the writer flush the close frame queued by the broadcast task
+            let _ = tokio::time::timeout(Duration::from_secs(1), &mut handle3).await;
+        },
+        _ = &mut handle2 => {},
+        _ = &mut handle3 => {},
+    }
+    handle1.abort();
+    handle2.abort();
+    handle3.abort();
+    // the room's channel is only closed once this receiver is dropped
+    let _ = handle1.await;
+
+    leave_room(room_number, username, state.rooms.clone(), &*state.pubsub).await;
+}
+
+// usernames are unique per room on this instance only, with the postgres
+// backend the same name can join the room through another instance
+pub async fn join_room(
+    room_number: u32,
+    username: String,
+    rooms: Arc<Mutex<HashMap<u32, RoomState>>>,
+) -> bool {
+    rooms
+        .lock()
+        .await
+        .entry(room_number)
+        .or_insert_with(|| RoomState {
+            users: Mutex::new(HashSet::new()),
+        })
+        .users
+        .lock()
+        .await
+        .insert(username)
+}
+
+// the channel is closed under the rooms lock so a concurrent join either
+// still finds the room or subscribes to a fresh channel
+pub async fn leave_room(
+    room_number: u32,
+    username: String,
+    rooms: Arc<Mutex<HashMap<u32, RoomState>>>,
+    pubsub: &dyn PubSub,
+) -> bool {
+    let mut no_room = false;
+    // if let Some(room_state) = rooms.lock().await.get(&room_number) {
+    let mut rooms = rooms.lock().await;
+    if let Some(room_state) = rooms.get(&room_number) {
+        let mut users = room_state.users.lock().await;
+        users.remove(&username);
+        if users.is_empty() {
+            no_room = true;
+        }
+    }
+    if no_room {
+        rooms.remove(&room_number);
+        pubsub.close(room_number).await;
+    }
+    println!("{} left room {}", username, room_number);
+    no_room
+}
+
+pub async fn broadcast_chat_message(
+    outbound: Arc<Outbound>,
+    username: String,
+    mut rx: Receiver<TweetMsg>,
+    policy: SlowConsumerPolicy,
+    metrics: Arc<ConnectionMetrics>,
+) {
+    // while let Ok(msg) = rx.recv().await {
+    loop {
+        match rx.recv().await {
+            Ok(msg) => match outbound.push(msg) {
+                Ok(0) => {}
+                Ok(dropped) => metrics.dropped(dropped),
+                Err(_) => {
+                    println!("[To {}] Outbound queue full, disconnecting", username);
+                    outbound.close(close_code::AGAIN, "too slow");
+                    break;
+                }
+            },
+            Err(RecvError::Lagged(skipped)) => {
+                metrics.lagged(skipped);
+                println!(
+                    "[To {}] Lagged behind, {} messages dropped",
+                    username, skipped
+                );
+                match policy {
+                    SlowConsumerPolicy::DropOldest => {}
+                    SlowConsumerPolicy::SkipWithNotice => outbound.skip(skipped),
+                    SlowConsumerPolicy::Disconnect => {
+                        outbound.close(close_code::AGAIN, "too slow");
+                        break;
+                    }
+                }
+            }
+            Err(e) => {
+                println!("[To {}] Error rx recv(): {}", username, e);
+                outbound.close(close_code::AWAY, "room closed");
+                break;
+            }
+        }
+    }
+}
+
+pub async fn write_chat_frames(
+    mut sender: SplitSink<WebSocket, Message>,
+    outbound: Arc<Outbound>,
+    username: String,
+    config: ChatConfig,
+    pubsub: Arc<dyn PubSub>,
+    metrics: Arc<ConnectionMetrics>,
+    last_seen: Arc<std::sync::Mutex<Instant>>,
+) {
+    let mut heartbeat = tokio::time::interval(config.heartbeat);
+    // the first tick completes immediately
+    heartbeat.tick().await;
+
+    loop {
+        let frame = tokio::select! {
+            frame = outbound.pop() => frame,
+            _ = heartbeat.tick() => {
+                if last_seen.lock().unwrap().elapsed() > config.idle_timeout {
+                    println!("[To {}] Idle timeout", username);
+                    Frame::Close(close_code::AWAY, "idle timeout")
+                } else {
+                    if let Err(e) = sender.send(Message::Ping(Vec::new())).await {
+                        println!("[To {}] Error sending ping: {}", username, e);
+                        break;
+                    }
+                    continue;
+                }
+            }
+        };
+
+        let (msg, is_tweet) = match frame {
+            Frame::Tweet(msg) => (Message::from(serde_json::to_string(&msg).unwrap()), true),
+            Frame::Gap(skipped) => (
+                Message::from(json!({ "skipped": skipped }).to_string()),
+                false,
+            ),
+            Frame::Close(code, reason) => {
+                let _ = sender
+                    .send(Message::Close(Some(CloseFrame {
+                        code,
+                        reason: Cow::from(reason),
+                    })))
+                    .await;
+                break;
+            }
+        };
+        if let Err(e) = sender.send(msg).await {
+            println!("[To {}] Error broadcasting chat message: {}", username, e);
+            break;
+        }
+        if is_tweet {
+            metrics.delivered();
+            if let Err(e) = pubsub.add_views(1).await {
+                println!("[To {}] Error counting views: {}", username, e);
+            }
+        }
+    }
+}
+
+pub async fn send_chat_messages(
+    mut receiver: SplitStream<WebSocket>,
+    room_number: u32,
+    tx: Sender<TweetMsg>,
+    pubsub: Arc<dyn PubSub>,
+    username: String,
+    metrics: Arc<ConnectionMetrics>,
+    last_seen: Arc<std::sync::Mutex<Instant>>,
+) {
+    while let Some(Ok(msg)) = receiver.next().await {
+        // any frame, including pongs, counts as a sign of life
+        *last_seen.lock().unwrap() = Instant::now();
+        if let Message::Close(_) = msg {
+            break;
+        }
+        // binary frames that are not utf-8 are ignored like malformed json
+        let Ok(msg) = msg.to_text() else {
+            continue;
+        };
+
+        let Ok(msg) = serde_json::from_str::<ChatMsg>(msg) else {
+            continue;
+        };
+        let msg = msg.message;
+        if msg.len() > 128 {
+            continue;
+        }
+
+        let msg = TweetMsg {
+            user: username.clone(),
+            message: msg,
+        };
+        if let Err(e) = pubsub.publish(room_number, &tx, msg).await {
+            println!("[From {}] Error sending chat message: {}", username, e);
+            break;
+        }
+        metrics.sent();
+    }
+}
@@ -0,0 +1,115 @@
+use std::{collections::VecDeque, mem, str::FromStr, sync::Mutex};
+
+use tokio::sync::Notify;
+
+use super::TweetMsg;
+
+// what to do with a client that can't keep up with its room
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlowConsumerPolicy {
+    DropOldest,
+    SkipWithNotice,
+    Disconnect,
+}
+
+impl FromStr for SlowConsumerPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop_oldest" => Ok(Self::DropOldest),
+            "skip" => Ok(Self::SkipWithNotice),
+            "disconnect" => Ok(Self::Disconnect),
+            _ => Err(format!("unknown slow consumer policy: {}", s)),
+        }
+    }
+}
+
+pub enum Frame {
+    Tweet(TweetMsg),
+    // number of messages the client missed
+    Gap(u64),
+    Close(u16, &'static str),
+}
+
+#[derive(Debug)]
+pub struct Overflow;
+
+struct Queue {
+    frames: VecDeque<Frame>,
+    skipped: u64,
+}
+
+// bounded per connection queue between the room and the socket writer
+pub struct Outbound {
+    queue: Mutex<Queue>,
+    notify: Notify,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+}
+
+impl Outbound {
+    pub fn new(capacity: usize, policy: SlowConsumerPolicy) -> Self {
+        Self {
+            queue: Mutex::new(Queue {
+                frames: VecDeque::new(),
+                skipped: 0,
+            }),
+            notify: Notify::new(),
+            capacity,
+            policy,
+        }
+    }
+
+    // returns how many messages were dropped to make room
+    pub fn push(&self, tweet: TweetMsg) -> Result<u64, Overflow> {
+        let mut queue = self.queue.lock().unwrap();
+        let mut dropped = 0;
+        if queue.frames.len() >= self.capacity {
+            match self.policy {
+                SlowConsumerPolicy::DropOldest => {
+                    queue.frames.pop_front();
+                    dropped = 1;
+                }
+                SlowConsumerPolicy::SkipWithNotice => {
+                    queue.skipped += 1;
+                    return Ok(1);
+                }
+                SlowConsumerPolicy::Disconnect => return Err(Overflow),
+            }
+        }
+        if queue.skipped > 0 {
+            let skipped = mem::take(&mut queue.skipped);
+            queue.frames.push_back(Frame::Gap(skipped));
+        }
+        queue.frames.push_back(Frame::Tweet(tweet));
+        drop(queue);
+
+        self.notify.notify_one();
+        Ok(dropped)
+    }
+
+    // announce missed messages before the next tweet
+    pub fn skip(&self, skipped: u64) {
+        self.queue.lock().unwrap().skipped += skipped;
+    }
+
+    pub fn close(&self, code: u16, reason: &'static str) {
+        self.queue
+            .lock()
+            .unwrap()
+            .frames
+            .push_back(Frame::Close(code, reason));
+        self.notify.notify_one();
+    }
+
+    // cancel safe, a frame is only taken out of the queue when it's returned
+    pub async fn pop(&self) -> Frame {
+        loop {
+            if let Some(frame) = self.queue.lock().unwrap().frames.pop_front() {
+                return frame;
+            }
+            self.notify.notified().await;
+        }
+    }
+}
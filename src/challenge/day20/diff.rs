@@ -0,0 +1,117 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::HeaderMap,
+    Json,
+};
+use git2::{Delta, DiffFindOptions, DiffOptions, Patch, Repository};
+use serde::{Deserialize, Serialize};
+
+use super::{resolve_commit, unpack, upload::with_upload, ArchiveConfig, ArchiveError};
+
+#[derive(Deserialize, Debug)]
+pub struct DiffQuery {
+    from: String,
+    // HEAD when omitted
+    to: Option<String>,
+    // include unified diff text per file
+    #[serde(default)]
+    patch: bool,
+    context: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FileDiff {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_path: Option<String>,
+    status: &'static str,
+    binary: bool,
+    additions: usize,
+    deletions: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    patch: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DiffSummary {
+    from: String,
+    to: String,
+    additions: usize,
+    deletions: usize,
+    files: Vec<FileDiff>,
+}
+
+fn status_name(status: Delta) -> &'static str {
+    match status {
+        Delta::Added => "added",
+        Delta::Deleted => "deleted",
+        Delta::Modified => "modified",
+        Delta::Renamed => "renamed",
+        Delta::Copied => "copied",
+        Delta::Typechange => "typechange",
+        _ => "other",
+    }
+}
+
+fn diff_commits(repo: &Repository, query: &DiffQuery) -> Result<DiffSummary, ArchiveError> {
+    let from = resolve_commit(repo, Some(&query.from))?;
+    let to = resolve_commit(repo, query.to.as_deref())?;
+
+    let mut options = DiffOptions::new();
+    options.context_lines(query.context.unwrap_or(3));
+    let mut diff =
+        repo.diff_tree_to_tree(Some(&from.tree()?), Some(&to.tree()?), Some(&mut options))?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+    let mut summary = DiffSummary {
+        from: from.id().to_string(),
+        to: to.id().to_string(),
+        additions: 0,
+        deletions: 0,
+        files: Vec::new(),
+    };
+    for (idx, delta) in diff.deltas().enumerate() {
+        let path = |file: git2::DiffFile| file.path().map(|path| path.display().to_string());
+        let new_path = path(delta.new_file());
+        let old_path = path(delta.old_file());
+
+        let (mut additions, mut deletions, mut text) = (0, 0, None);
+        if let Some(mut patch) = Patch::from_diff(&diff, idx)? {
+            (_, additions, deletions) = patch.line_stats()?;
+            if query.patch {
+                let buf = patch.to_buf()?;
+                text = Some(String::from_utf8_lossy(&buf).into_owned());
+            }
+        }
+        summary.additions += additions;
+        summary.deletions += deletions;
+
+        summary.files.push(FileDiff {
+            path: new_path.clone().or(old_path.clone()).unwrap_or_default(),
+            old_path: old_path.filter(|old_path| Some(old_path) != new_path.as_ref()),
+            status: status_name(delta.status()),
+            binary: delta.flags().is_binary(),
+            additions,
+            deletions,
+            patch: text,
+        });
+    }
+    Ok(summary)
+}
+
+pub async fn diff(
+    State(config): State<ArchiveConfig>,
+    headers: HeaderMap,
+    Query(query): Query<DiffQuery>,
+    body: Body,
+) -> Result<Json<DiffSummary>, ArchiveError> {
+    let max_upload_size = config.max_upload_size;
+    let result = with_upload(body, &headers, max_upload_size, move |reader, format| {
+        let dir = unpack(reader, format, &config)?;
+        let repo = Repository::open(dir.path())?;
+        diff_commits(&repo, &query)
+    })
+    .await?;
+    Ok(Json(result))
+}
@@ -0,0 +1,191 @@
+use std::{
+    io::{self, Seek, Write},
+    path::Path,
+};
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    response::IntoResponse,
+};
+use flate2::{write::GzEncoder, Compression};
+use git2::{ObjectType, Repository, TreeWalkMode, TreeWalkResult};
+use serde::Deserialize;
+use tar::{Builder, EntryType, Header};
+use tokio_util::io::ReaderStream;
+
+use super::{escapes, resolve_commit, unpack, upload::with_upload, ArchiveConfig, ArchiveError};
+
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    #[default]
+    #[serde(rename = "tar")]
+    Tar,
+    #[serde(rename = "tar.gz", alias = "tgz")]
+    TarGz,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Tar => "application/x-tar",
+            ExportFormat::TarGz => "application/gzip",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Tar => "tar",
+            ExportFormat::TarGz => "tar.gz",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    #[serde(rename = "ref")]
+    reference: Option<String>,
+    #[serde(default)]
+    format: ExportFormat,
+    // prepended to every path, like `git archive --prefix`
+    prefix: Option<String>,
+}
+
+// file modes as stored in git trees
+const MODE_EXECUTABLE: i32 = 0o100755;
+const MODE_SYMLINK: i32 = 0o120000;
+
+// every entry gets the commit time, as `git archive` does; the prefix is
+// checked beforehand, so errors of the builder come from writing
+fn write_tree<W: Write>(
+    repo: &Repository,
+    query: &ExportQuery,
+    writer: W,
+) -> Result<(String, W), ArchiveError> {
+    let commit = resolve_commit(repo, query.reference.as_deref())?;
+    let mtime = commit.time().seconds().max(0) as u64;
+    let prefix = query.prefix.as_deref().unwrap_or_default();
+
+    let mut builder = Builder::new(writer);
+    if prefix.ends_with('/') {
+        let mut header = Header::new_gnu();
+        header.set_mtime(mtime);
+        header.set_entry_type(EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, prefix, io::empty())
+            .map_err(ArchiveError::Storage)?;
+    }
+
+    let mut error = None;
+    let result = commit.tree()?.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let path = format!("{}{}{}", prefix, root, entry.name().unwrap_or_default());
+        let mut header = Header::new_gnu();
+        header.set_mtime(mtime);
+
+        let written = match entry.kind() {
+            Some(ObjectType::Tree) => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                builder.append_data(&mut header, format!("{}/", path), io::empty())
+            }
+            Some(ObjectType::Blob) => match entry.to_object(repo).and_then(|o| o.peel_to_blob()) {
+                Ok(blob) => {
+                    if entry.filemode() == MODE_SYMLINK {
+                        // a symlink blob holds the link target
+                        header.set_entry_type(EntryType::Symlink);
+                        header.set_mode(0o777);
+                        header.set_size(0);
+                        let target = String::from_utf8_lossy(blob.content()).into_owned();
+                        builder.append_link(&mut header, &path, target)
+                    } else {
+                        header.set_entry_type(EntryType::Regular);
+                        header.set_mode(if entry.filemode() == MODE_EXECUTABLE {
+                            0o755
+                        } else {
+                            0o644
+                        });
+                        header.set_size(blob.size() as u64);
+                        builder.append_data(&mut header, &path, blob.content())
+                    }
+                }
+                Err(e) => {
+                    error = Some(e.into());
+                    return TreeWalkResult::Abort;
+                }
+            },
+            // submodules point at commits of other repositories
+            _ => return TreeWalkResult::Skip,
+        };
+        match written {
+            Ok(()) => TreeWalkResult::Ok,
+            Err(e) => {
+                error = Some(ArchiveError::Storage(e));
+                TreeWalkResult::Abort
+            }
+        }
+    });
+    if let Some(e) = error {
+        return Err(e);
+    }
+    result?;
+
+    let writer = builder.into_inner().map_err(ArchiveError::Storage)?;
+    Ok((commit.id().to_string(), writer))
+}
+
+pub async fn export(
+    State(config): State<ArchiveConfig>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+    body: Body,
+) -> Result<impl IntoResponse, ArchiveError> {
+    if query
+        .prefix
+        .as_deref()
+        .is_some_and(|prefix| escapes(Path::new(prefix)))
+    {
+        return Err(ArchiveError::InvalidQuery(
+            "prefix must be a relative path".to_string(),
+        ));
+    }
+    let export_format = query.format;
+    let max_upload_size = config.max_upload_size;
+    // the archive is spooled to disk and streamed back from there
+    let (commit, file) = with_upload(body, &headers, max_upload_size, move |reader, format| {
+        let dir = unpack(reader, format, &config)?;
+        let repo = Repository::open(dir.path())?;
+        let file = tempfile::tempfile().map_err(ArchiveError::Storage)?;
+        let (commit, mut file) = match query.format {
+            ExportFormat::Tar => write_tree(&repo, &query, file)?,
+            ExportFormat::TarGz => {
+                let (commit, encoder) =
+                    write_tree(&repo, &query, GzEncoder::new(file, Compression::default()))?;
+                (commit, encoder.finish().map_err(ArchiveError::Storage)?)
+            }
+        };
+        file.rewind().map_err(ArchiveError::Storage)?;
+        Ok((commit, file))
+    })
+    .await?;
+
+    let file = tokio::fs::File::from_std(file);
+    let disposition = format!(
+        "attachment; filename=\"{}.{}\"",
+        &commit[..12],
+        export_format.extension()
+    );
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                export_format.content_type().to_string(),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    ))
+}
@@ -0,0 +1,192 @@
+use std::{
+    io::{self, Read, Seek},
+    path::PathBuf,
+};
+
+use axum::http::{header, HeaderMap};
+use chrono::NaiveDateTime;
+use tar::{Archive, EntryType};
+use zip::ZipArchive;
+
+use super::{copy_to_file, ArchiveError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarZst,
+    TarXz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    // magic bytes win over the declared content type, plain tar otherwise
+    pub fn detect(body: &[u8], headers: &HeaderMap) -> Self {
+        Self::from_magic(body)
+            .or_else(|| Self::from_content_type(headers))
+            .unwrap_or(Self::Tar)
+    }
+
+    fn from_magic(body: &[u8]) -> Option<Self> {
+        if body.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else if body.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::TarZst)
+        } else if body.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::TarXz)
+        } else if body.starts_with(b"PK\x03\x04") || body.starts_with(b"PK\x05\x06") {
+            Some(Self::Zip)
+        } else if body.get(257..262) == Some(b"ustar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+
+    fn from_content_type(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        match content_type.split(';').next()?.trim() {
+            "application/x-tar" => Some(Self::Tar),
+            "application/gzip" | "application/x-gzip" => Some(Self::TarGz),
+            "application/zstd" => Some(Self::TarZst),
+            "application/x-xz" => Some(Self::TarXz),
+            "application/zip" | "application/x-zip-compressed" => Some(Self::Zip),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    Hardlink,
+    Other(&'static str),
+}
+
+impl EntryKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Directory => "directory",
+            EntryKind::Symlink => "symlink",
+            EntryKind::Hardlink => "hardlink",
+            EntryKind::Other(name) => name,
+        }
+    }
+}
+
+impl From<EntryType> for EntryKind {
+    fn from(entry_type: EntryType) -> Self {
+        match entry_type {
+            EntryType::Regular | EntryType::Continuous => EntryKind::File,
+            EntryType::Directory => EntryKind::Directory,
+            EntryType::Symlink => EntryKind::Symlink,
+            EntryType::Link => EntryKind::Hardlink,
+            EntryType::Char => EntryKind::Other("char"),
+            EntryType::Block => EntryKind::Other("block"),
+            EntryType::Fifo => EntryKind::Other("fifo"),
+            _ => EntryKind::Other("other"),
+        }
+    }
+}
+
+// format independent view of an archive entry, paths are not sanitized
+#[derive(Debug)]
+pub struct EntryInfo {
+    pub path: PathBuf,
+    pub size: u64,
+    pub mode: Option<u32>,
+    pub mtime: Option<u64>,
+    pub kind: EntryKind,
+    pub link_target: Option<String>,
+}
+
+// calls `f` once per entry in archive order with a reader over its content
+pub fn walk<R, F>(mut reader: R, format: ArchiveFormat, f: F) -> Result<(), ArchiveError>
+where
+    R: Read,
+    F: FnMut(EntryInfo, &mut dyn Read) -> Result<(), ArchiveError>,
+{
+    match format {
+        ArchiveFormat::Tar => walk_tar(reader, f),
+        ArchiveFormat::TarGz => walk_tar(flate2::read::MultiGzDecoder::new(reader), f),
+        ArchiveFormat::TarZst => walk_tar(zstd::stream::read::Decoder::new(reader)?, f),
+        ArchiveFormat::TarXz => walk_tar(xz2::read::XzDecoder::new(reader), f),
+        ArchiveFormat::Zip => {
+            // the central directory sits at the end, spool to disk to seek
+            let mut file = tempfile::tempfile().map_err(ArchiveError::Storage)?;
+            copy_to_file(&mut reader, &mut file)?;
+            file.rewind().map_err(ArchiveError::Storage)?;
+            walk_zip(file, f)
+        }
+    }
+}
+
+fn walk_tar<R, F>(reader: R, mut f: F) -> Result<(), ArchiveError>
+where
+    R: Read,
+    F: FnMut(EntryInfo, &mut dyn Read) -> Result<(), ArchiveError>,
+{
+    let mut archive = Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let header = entry.header();
+        let info = EntryInfo {
+            path: entry.path()?.into_owned(),
+            size: entry.size(),
+            mode: header.mode().ok(),
+            mtime: header.mtime().ok(),
+            kind: header.entry_type().into(),
+            link_target: entry
+                .link_name()?
+                .map(|target| target.display().to_string()),
+        };
+        f(info, &mut entry)?;
+    }
+    Ok(())
+}
+
+fn walk_zip<R, F>(reader: R, mut f: F) -> Result<(), ArchiveError>
+where
+    R: Read + Seek,
+    F: FnMut(EntryInfo, &mut dyn Read) -> Result<(), ArchiveError>,
+{
+    let mut archive = ZipArchive::new(reader)?;
+    for idx in 0..archive.len() {
+        let mut file = archive.by_index(idx)?;
+        let kind = if file.is_dir() {
+            EntryKind::Directory
+        } else if file.is_symlink() {
+            EntryKind::Symlink
+        } else {
+            EntryKind::File
+        };
+        // zip stores the target of a symlink as its content
+        let link_target = if kind == EntryKind::Symlink {
+            let mut target = String::new();
+            file.read_to_string(&mut target)?;
+            Some(target)
+        } else {
+            None
+        };
+        let info = EntryInfo {
+            path: PathBuf::from(file.name()),
+            size: file.size(),
+            mode: file.unix_mode(),
+            mtime: file
+                .last_modified()
+                .and_then(|dt| NaiveDateTime::try_from(dt).ok())
+                .map(|dt| dt.and_utc().timestamp() as u64),
+            kind,
+            link_target,
+        };
+        if kind == EntryKind::Symlink {
+            f(info, &mut io::empty())?;
+        } else {
+            f(info, &mut file)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, io};
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::HeaderMap,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    format::{walk, ArchiveFormat, EntryKind},
+    upload::{with_upload, UploadReader},
+    ArchiveConfig, ArchiveError,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct ManifestQuery {
+    #[serde(default)]
+    checksums: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ManifestEntry {
+    path: String,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<u64>,
+    entry_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link_target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Aggregate {
+    files: usize,
+    size: u64,
+}
+
+impl Aggregate {
+    fn add(&mut self, size: u64) {
+        self.files += 1;
+        self.size += size;
+    }
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct Manifest {
+    total: Aggregate,
+    by_extension: BTreeMap<String, Aggregate>,
+    by_directory: BTreeMap<String, Aggregate>,
+    entries: Vec<ManifestEntry>,
+}
+
+// single pass, entry contents are only read when checksums are asked for
+fn build_manifest(
+    reader: UploadReader,
+    format: ArchiveFormat,
+    checksums: bool,
+    config: &ArchiveConfig,
+) -> Result<Manifest, ArchiveError> {
+    let mut manifest = Manifest::default();
+    walk(reader, format, |info, reader| {
+        if manifest.entries.len() >= config.max_entries {
+            return Err(ArchiveError::TooManyEntries(config.max_entries));
+        }
+        let is_file = info.kind == EntryKind::File;
+
+        let sha256 = if checksums && is_file {
+            let mut hasher = Sha256::new();
+            io::copy(reader, &mut hasher)?;
+            Some(base16ct::lower::encode_string(&hasher.finalize()))
+        } else {
+            None
+        };
+
+        if is_file {
+            let extension = info
+                .path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let directory = info
+                .path
+                .parent()
+                .map(|parent| parent.display().to_string())
+                .filter(|parent| !parent.is_empty())
+                .unwrap_or(".".to_string());
+            manifest.total.add(info.size);
+            manifest
+                .by_extension
+                .entry(extension)
+                .or_default()
+                .add(info.size);
+            manifest
+                .by_directory
+                .entry(directory)
+                .or_default()
+                .add(info.size);
+        }
+
+        manifest.entries.push(ManifestEntry {
+            path: info.path.display().to_string(),
+            size: info.size,
+            mode: info.mode.map(|mode| format!("{:o}", mode)),
+            mtime: info.mtime,
+            entry_type: info.kind.name(),
+            link_target: info.link_target,
+            sha256,
+        });
+        Ok(())
+    })?;
+    Ok(manifest)
+}
+
+pub async fn manifest(
+    State(config): State<ArchiveConfig>,
+    headers: HeaderMap,
+    Query(query): Query<ManifestQuery>,
+    body: Body,
+) -> Result<Json<Manifest>, ArchiveError> {
+    let max_upload_size = config.max_upload_size;
+    let manifest = with_upload(body, &headers, max_upload_size, move |reader, format| {
+        build_manifest(reader, format, query.checksums, &config)
+    })
+    .await?;
+    Ok(Json(manifest))
+}
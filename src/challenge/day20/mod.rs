@@ -0,0 +1,348 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Component, Path},
+};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use chrono::DateTime;
+use git2::{BranchType, ErrorCode, ObjectType, Repository, Sort, TreeWalkMode, TreeWalkResult};
+use itertools::Itertools;
+use shuttle_runtime::SecretStore;
+use tempfile::TempDir;
+
+use format::{walk, ArchiveFormat, EntryKind};
+use upload::{with_upload, UploadReader};
+
+use crate::util::parse_secret;
+
+pub mod diff;
+pub mod export;
+pub mod format;
+pub mod manifest;
+pub mod search;
+pub mod stats;
+pub mod upload;
+
+#[derive(Clone, Debug)]
+pub struct ArchiveConfig {
+    // bytes read from the request, compressed size for compressed archives
+    pub max_upload_size: u64,
+    // limits for archives unpacked to disk
+    pub max_entries: usize,
+    pub max_unpacked_size: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            max_upload_size: 1024 * 1024 * 1024,
+            max_entries: 10000,
+            max_unpacked_size: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl ArchiveConfig {
+    pub fn from_secrets(secrets: &SecretStore) -> anyhow::Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            max_upload_size: parse_secret(
+                secrets,
+                "ARCHIVE_MAX_UPLOAD_SIZE",
+                default.max_upload_size,
+            )?,
+            max_entries: parse_secret(secrets, "ARCHIVE_MAX_ENTRIES", default.max_entries)?,
+            max_unpacked_size: parse_secret(
+                secrets,
+                "ARCHIVE_MAX_UNPACKED_SIZE",
+                default.max_unpacked_size,
+            )?,
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveError {
+    // reading the upload, the archive is malformed or truncated
+    #[error("malformed archive: {0}")]
+    Io(#[from] std::io::Error),
+    // writing to local disk
+    #[error("storage error: {0}")]
+    Storage(std::io::Error),
+    #[error("git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error("zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("archive task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("upload larger than {0} bytes")]
+    UploadTooLarge(u64),
+    #[error("archive has more than {0} entries")]
+    TooManyEntries(usize),
+    #[error("archive unpacks to more than {0} bytes")]
+    TooLarge(u64),
+    #[error("unsafe path in archive: {0}")]
+    UnsafePath(String),
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("{0} is not valid utf-8")]
+    NotUtf8(String),
+}
+
+impl IntoResponse for ArchiveError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ArchiveError::Io(_)
+            | ArchiveError::Git(_)
+            | ArchiveError::Zip(_)
+            | ArchiveError::UnsafePath(_)
+            | ArchiveError::InvalidQuery(_)
+            | ArchiveError::NotUtf8(_) => StatusCode::BAD_REQUEST,
+            ArchiveError::TooManyEntries(_)
+            | ArchiveError::TooLarge(_)
+            | ArchiveError::UploadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ArchiveError::NotFound(_) => StatusCode::NOT_FOUND,
+            ArchiveError::Storage(_) | ArchiveError::Task(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+pub fn routes(config: ArchiveConfig) -> Router {
+    Router::new()
+        .route("/archive_files", post(get_archive_file_nums))
+        .route("/archive_files_size", post(get_archive_file_size))
+        .route("/cookie", post(get_cookie))
+        .route("/diff", post(diff::diff))
+        .route("/export", post(export::export))
+        .route("/manifest", post(manifest::manifest))
+        .route("/search", post(search::search))
+        .route("/stats", post(stats::stats))
+        .with_state(config)
+}
+
+pub async fn get_archive_file_nums(
+    State(config): State<ArchiveConfig>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<String, ArchiveError> {
+    let count = with_upload(body, &headers, config.max_upload_size, |reader, format| {
+        let mut count = 0;
+        walk(reader, format, |_, _| {
+            count += 1;
+            Ok(())
+        })?;
+        Ok(count)
+    })
+    .await?;
+    Ok(count.to_string())
+}
+
+pub async fn get_archive_file_size(
+    State(config): State<ArchiveConfig>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<String, ArchiveError> {
+    let size = with_upload(body, &headers, config.max_upload_size, |reader, format| {
+        let mut size = 0;
+        walk(reader, format, |info, _| {
+            size += info.size;
+            Ok(())
+        })?;
+        Ok(size)
+    })
+    .await?;
+    Ok(size.to_string())
+}
+
+// need to walk subtree (ie. subfolder)
+fn find_cookie(commit: &git2::Commit, repo: &git2::Repository) -> Result<bool, ArchiveError> {
+    let mut found_it = false;
+    let mut error = None;
+    let result = commit.tree()?.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.name() != Some("santa.txt") || entry.kind() != Some(ObjectType::Blob) {
+            return TreeWalkResult::Ok;
+        }
+        let blob = match entry
+            .to_object(repo)
+            .and_then(|object| object.peel_to_blob())
+        {
+            Ok(blob) => blob,
+            Err(e) => {
+                error = Some(e.into());
+                return TreeWalkResult::Abort;
+            }
+        };
+        match std::str::from_utf8(blob.content()) {
+            Ok(content) => {
+                found_it |= content.contains("COOKIE");
+                TreeWalkResult::Ok
+            }
+            Err(_) => {
+                error = Some(ArchiveError::NotUtf8(format!("{}santa.txt", root)));
+                TreeWalkResult::Abort
+            }
+        }
+    });
+    if let Some(e) = error {
+        return Err(e);
+    }
+    result?;
+    Ok(found_it)
+}
+
+// `..`, absolute or drive prefixed paths would point outside the target
+pub fn escapes(path: &Path) -> bool {
+    path.components().any(|c| {
+        matches!(
+            c,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    })
+}
+
+// a local file system failure, unless entries of the archive conflict with
+// each other, like a file and a directory of the same name
+fn storage_error(e: io::Error) -> ArchiveError {
+    match e.kind() {
+        io::ErrorKind::AlreadyExists
+        | io::ErrorKind::NotADirectory
+        | io::ErrorKind::IsADirectory => ArchiveError::Io(e),
+        _ => ArchiveError::Storage(e),
+    }
+}
+
+// like `io::copy`, read errors are the upload's, write errors the disk's
+pub fn copy_to_file<R: Read + ?Sized>(reader: &mut R, file: &mut File) -> Result<(), ArchiveError> {
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        file.write_all(&buf[..n]).map_err(ArchiveError::Storage)?;
+    }
+}
+
+// unpack into a fresh temporary directory, removed when the TempDir is dropped
+fn unpack(
+    reader: UploadReader,
+    format: ArchiveFormat,
+    config: &ArchiveConfig,
+) -> Result<TempDir, ArchiveError> {
+    let dir = TempDir::new().map_err(ArchiveError::Storage)?;
+    let mut entries = 0;
+    let mut unpacked_size = 0;
+    walk(reader, format, |info, reader| {
+        entries += 1;
+        if entries > config.max_entries {
+            return Err(ArchiveError::TooManyEntries(config.max_entries));
+        }
+        unpacked_size += info.size;
+        if unpacked_size > config.max_unpacked_size {
+            return Err(ArchiveError::TooLarge(config.max_unpacked_size));
+        }
+
+        if escapes(&info.path) {
+            return Err(ArchiveError::UnsafePath(info.path.display().to_string()));
+        }
+
+        let target = dir.path().join(&info.path);
+        match info.kind {
+            EntryKind::Directory => fs::create_dir_all(&target).map_err(storage_error)?,
+            EntryKind::File => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(storage_error)?;
+                }
+                let mut file = File::create(&target).map_err(storage_error)?;
+                copy_to_file(&mut reader.take(info.size), &mut file)?;
+            }
+            // links are never created, so nothing can be written outside dir
+            _ => {}
+        }
+        Ok(())
+    })?;
+    Ok(dir)
+}
+
+// branch, tag or commit hash, HEAD when omitted
+fn resolve_commit<'repo>(
+    repo: &'repo Repository,
+    reference: Option<&str>,
+) -> Result<git2::Commit<'repo>, ArchiveError> {
+    let object = match reference {
+        Some(reference) => repo.revparse_single(reference),
+        None => repo
+            .head()
+            .and_then(|head| head.peel(git2::ObjectType::Any)),
+    };
+    match object.and_then(|object| object.peel_to_commit()) {
+        Ok(commit) => Ok(commit),
+        Err(e) if e.code() == ErrorCode::NotFound => Err(ArchiveError::NotFound("ref")),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn format_time(time: git2::Time) -> String {
+    DateTime::from_timestamp(time.seconds(), 0)
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+fn find_cookie_commit(repo: &Repository) -> Result<String, ArchiveError> {
+    // get branch ref
+    let branch = repo.find_branch("christmas", BranchType::Local)?;
+    let branch_ref = branch.get();
+
+    // traverse tree (including subtree)
+    let mut revwalk = repo.revwalk()?;
+    let _ = revwalk.set_sorting(Sort::TOPOLOGICAL);
+    revwalk.push_ref(branch_ref.name().ok_or(ArchiveError::NotFound("branch"))?)?;
+    let commits = revwalk
+        .map(|x| repo.find_commit(x?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut found = None;
+    for commit in commits
+        .into_iter()
+        .sorted_by(|a, b| b.time().cmp(&a.time()))
+    {
+        if find_cookie(&commit, repo)? {
+            found = Some(commit);
+            break;
+        }
+    }
+    let commit = found.ok_or(ArchiveError::NotFound("cookie"))?;
+
+    // get commit info
+    let committer = commit.committer().name().unwrap_or_default().to_string();
+    let hash = commit.id().to_string();
+
+    Ok(format!("{} {}", committer, hash))
+}
+
+pub async fn get_cookie(
+    State(config): State<ArchiveConfig>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<String, ArchiveError> {
+    let max_upload_size = config.max_upload_size;
+    with_upload(body, &headers, max_upload_size, move |reader, format| {
+        // unpack and open archive
+        let dir = unpack(reader, format, &config)?;
+        let repo = Repository::open(dir.path())?;
+        find_cookie_commit(&repo)
+    })
+    .await
+}
@@ -0,0 +1,151 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::HeaderMap,
+    Json,
+};
+use git2::{Repository, Sort, TreeWalkMode, TreeWalkResult};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    format_time, resolve_commit, unpack, upload::with_upload, ArchiveConfig, ArchiveError,
+};
+
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    // oldest commit first
+    #[default]
+    Chronological,
+    // parents before their children
+    Topological,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchQuery {
+    #[serde(rename = "ref")]
+    reference: Option<String>,
+    path: Option<String>,
+    pattern: String,
+    #[serde(default)]
+    regex: bool,
+    #[serde(default)]
+    order: Order,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchMatch {
+    commit: String,
+    author: String,
+    committer: String,
+    time: String,
+    path: String,
+    line_number: usize,
+    line: String,
+}
+
+enum Pattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn is_match(&self, line: &str) -> bool {
+        match self {
+            Pattern::Literal(pattern) => line.contains(pattern.as_str()),
+            Pattern::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+const DEFAULT_LIMIT: usize = 1000;
+
+fn search_history(
+    repo: &Repository,
+    query: &SearchQuery,
+) -> Result<Vec<SearchMatch>, ArchiveError> {
+    let pattern = if query.regex {
+        let regex =
+            Regex::new(&query.pattern).map_err(|e| ArchiveError::InvalidQuery(e.to_string()))?;
+        Pattern::Regex(regex)
+    } else {
+        Pattern::Literal(query.pattern.clone())
+    };
+    let glob: GlobMatcher = GlobBuilder::new(query.path.as_deref().unwrap_or("**"))
+        .literal_separator(true)
+        .build()
+        .map_err(|e| ArchiveError::InvalidQuery(e.to_string()))?
+        .compile_matcher();
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let start = resolve_commit(repo, query.reference.as_deref())?;
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(match query.order {
+        Order::Chronological => Sort::TIME | Sort::REVERSE,
+        Order::Topological => Sort::TOPOLOGICAL | Sort::REVERSE,
+    })?;
+    revwalk.push(start.id())?;
+
+    let mut matches = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let mut hits = Vec::new();
+        commit.tree()?.walk(TreeWalkMode::PreOrder, |root, entry| {
+            let path = format!("{}{}", root, entry.name().unwrap_or_default());
+            if !glob.is_match(&path) {
+                return TreeWalkResult::Ok;
+            }
+            let Some(blob) = entry
+                .to_object(repo)
+                .ok()
+                .and_then(|object| object.into_blob().ok())
+            else {
+                return TreeWalkResult::Ok;
+            };
+            let Ok(content) = std::str::from_utf8(blob.content()) else {
+                return TreeWalkResult::Ok;
+            };
+            for (idx, line) in content.lines().enumerate() {
+                if pattern.is_match(line) {
+                    hits.push((path.clone(), idx + 1, line.to_string()));
+                }
+            }
+            TreeWalkResult::Ok
+        })?;
+
+        for (path, line_number, line) in hits {
+            matches.push(SearchMatch {
+                commit: commit.id().to_string(),
+                author: commit.author().name().unwrap_or_default().to_string(),
+                committer: commit.committer().name().unwrap_or_default().to_string(),
+                time: format_time(commit.time()),
+                path,
+                line_number,
+                line,
+            });
+            if matches.len() >= limit {
+                return Ok(matches);
+            }
+        }
+    }
+    Ok(matches)
+}
+
+pub async fn search(
+    State(config): State<ArchiveConfig>,
+    headers: HeaderMap,
+    Query(query): Query<SearchQuery>,
+    body: Body,
+) -> Result<Json<Vec<SearchMatch>>, ArchiveError> {
+    let max_upload_size = config.max_upload_size;
+    let result = with_upload(body, &headers, max_upload_size, move |reader, format| {
+        let dir = unpack(reader, format, &config)?;
+        let repo = Repository::open(dir.path())?;
+        search_history(&repo, &query)
+    })
+    .await?;
+    Ok(Json(result))
+}
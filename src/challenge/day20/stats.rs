@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::HeaderMap,
+    Json,
+};
+use chrono::DateTime;
+use git2::{BranchType, Patch, Repository, Sort};
+use serde::{Deserialize, Serialize};
+
+use super::{resolve_commit, unpack, upload::with_upload, ArchiveConfig, ArchiveError};
+
+#[derive(Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    fn key(&self, seconds: i64) -> String {
+        let dt = DateTime::from_timestamp(seconds, 0).unwrap_or_default();
+        match self {
+            Bucket::Day => dt.format("%Y-%m-%d").to_string(),
+            Bucket::Week => dt.format("%G-W%V").to_string(),
+            Bucket::Month => dt.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StatsQuery {
+    // only history reachable from this ref, every branch and tag when omitted
+    #[serde(rename = "ref")]
+    reference: Option<String>,
+    #[serde(default)]
+    bucket: Bucket,
+    // entries of each ranking, authors, churn and largest blobs
+    top: Option<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AuthorStats {
+    name: String,
+    email: String,
+    commits: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RefInfo {
+    name: String,
+    commit: String,
+}
+
+#[derive(Serialize, Default, Debug)]
+pub struct FileChurn {
+    path: String,
+    commits: usize,
+    additions: usize,
+    deletions: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BlobInfo {
+    path: String,
+    oid: String,
+    size: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RepoStats {
+    commits: usize,
+    authors: Vec<AuthorStats>,
+    branches: Vec<RefInfo>,
+    tags: Vec<RefInfo>,
+    churn: Vec<FileChurn>,
+    largest_blobs: Vec<BlobInfo>,
+    timeline: BTreeMap<String, usize>,
+}
+
+const DEFAULT_TOP: usize = 10;
+
+fn list_refs(repo: &Repository) -> Result<(Vec<RefInfo>, Vec<RefInfo>), ArchiveError> {
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let (Some(name), Ok(commit)) = (branch.name()?, branch.get().peel_to_commit()) else {
+            continue;
+        };
+        branches.push(RefInfo {
+            name: name.to_string(),
+            commit: commit.id().to_string(),
+        });
+    }
+
+    let mut tags = Vec::new();
+    for name in repo.tag_names(None)?.iter().flatten() {
+        let Ok(commit) = repo
+            .revparse_single(&format!("refs/tags/{}", name))
+            .and_then(|object| object.peel_to_commit())
+        else {
+            continue;
+        };
+        tags.push(RefInfo {
+            name: name.to_string(),
+            commit: commit.id().to_string(),
+        });
+    }
+    Ok((branches, tags))
+}
+
+fn repo_stats(repo: &Repository, query: &StatsQuery) -> Result<RepoStats, ArchiveError> {
+    let (branches, tags) = list_refs(repo)?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TIME)?;
+    match &query.reference {
+        Some(reference) => revwalk.push(resolve_commit(repo, Some(reference))?.id())?,
+        None => {
+            revwalk.push_glob("refs/heads/*")?;
+            revwalk.push_glob("refs/tags/*")?;
+        }
+    }
+
+    let mut commits = 0;
+    let mut authors: HashMap<(String, String), usize> = HashMap::new();
+    let mut churn: HashMap<String, FileChurn> = HashMap::new();
+    let mut blobs: HashMap<git2::Oid, BlobInfo> = HashMap::new();
+    let mut timeline = BTreeMap::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        commits += 1;
+
+        let author = commit.author();
+        let key = (
+            author.name().unwrap_or_default().to_string(),
+            author.email().unwrap_or_default().to_string(),
+        );
+        *authors.entry(key).or_default() += 1;
+        *timeline
+            .entry(query.bucket.key(commit.time().seconds()))
+            .or_default() += 1;
+
+        // changes against the first parent, everything for a root commit
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        for (idx, delta) in diff.deltas().enumerate() {
+            let file = delta.new_file();
+            let Some(path) = file.path().or(delta.old_file().path()) else {
+                continue;
+            };
+            let path = path.display().to_string();
+
+            let entry = churn.entry(path.clone()).or_insert_with(|| FileChurn {
+                path: path.clone(),
+                ..Default::default()
+            });
+            entry.commits += 1;
+            if let Some(patch) = Patch::from_diff(&diff, idx)? {
+                let (_, additions, deletions) = patch.line_stats()?;
+                entry.additions += additions;
+                entry.deletions += deletions;
+            }
+
+            // every blob enters history through some delta
+            if !file.id().is_zero() {
+                blobs.entry(file.id()).or_insert_with(|| BlobInfo {
+                    path,
+                    oid: file.id().to_string(),
+                    size: file.size(),
+                });
+            }
+        }
+    }
+
+    let top = query.top.unwrap_or(DEFAULT_TOP);
+    let mut authors: Vec<AuthorStats> = authors
+        .into_iter()
+        .map(|((name, email), commits)| AuthorStats {
+            name,
+            email,
+            commits,
+        })
+        .collect();
+    authors.sort_by(|a, b| {
+        b.commits
+            .cmp(&a.commits)
+            .then_with(|| (&a.name, &a.email).cmp(&(&b.name, &b.email)))
+    });
+    authors.truncate(top);
+    let mut churn: Vec<FileChurn> = churn.into_values().collect();
+    churn.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.path.cmp(&b.path)));
+    churn.truncate(top);
+    let mut largest_blobs: Vec<BlobInfo> = blobs.into_values().collect();
+    largest_blobs.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    largest_blobs.truncate(top);
+
+    Ok(RepoStats {
+        commits,
+        authors,
+        branches,
+        tags,
+        churn,
+        largest_blobs,
+        timeline,
+    })
+}
+
+pub async fn stats(
+    State(config): State<ArchiveConfig>,
+    headers: HeaderMap,
+    Query(query): Query<StatsQuery>,
+    body: Body,
+) -> Result<Json<RepoStats>, ArchiveError> {
+    let max_upload_size = config.max_upload_size;
+    let result = with_upload(body, &headers, max_upload_size, move |reader, format| {
+        let dir = unpack(reader, format, &config)?;
+        let repo = Repository::open(dir.path())?;
+        repo_stats(&repo, &query)
+    })
+    .await?;
+    Ok(Json(result))
+}
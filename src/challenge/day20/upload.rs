@@ -0,0 +1,83 @@
+use std::{
+    io::{self, Cursor, Read},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use axum::{body::Body, http::HeaderMap};
+use futures_util::StreamExt;
+use tokio_util::io::{StreamReader, SyncIoBridge};
+
+use super::{format::ArchiveFormat, ArchiveError};
+
+use crate::util::content_length;
+
+// enough to see the tar "ustar" magic at offset 257
+const HEAD_SIZE: usize = 512;
+
+pub type UploadReader = Box<dyn Read + Send>;
+
+// fails reads once more than `remaining` bytes came through
+struct LimitedReader<R> {
+    inner: R,
+    remaining: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n as u64 > self.remaining {
+            self.exceeded.store(true, Ordering::Relaxed);
+            return Err(io::Error::other("upload too large"));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+// runs `f` on a blocking thread with the body as a synchronous reader, the
+// upload is never buffered as a whole
+pub async fn with_upload<T, F>(
+    body: Body,
+    headers: &HeaderMap,
+    max_upload_size: u64,
+    f: F,
+) -> Result<T, ArchiveError>
+where
+    T: Send + 'static,
+    F: FnOnce(UploadReader, ArchiveFormat) -> Result<T, ArchiveError> + Send + 'static,
+{
+    if content_length(headers).is_some_and(|length| length > max_upload_size) {
+        return Err(ArchiveError::UploadTooLarge(max_upload_size));
+    }
+
+    let stream = body
+        .into_data_stream()
+        .map(|chunk| chunk.map_err(io::Error::other));
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let mut reader = LimitedReader {
+        inner: SyncIoBridge::new(StreamReader::new(stream)),
+        remaining: max_upload_size,
+        exceeded: exceeded.clone(),
+    };
+    let headers = headers.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut head = Vec::with_capacity(HEAD_SIZE);
+        (&mut reader)
+            .take(HEAD_SIZE as u64)
+            .read_to_end(&mut head)?;
+        let format = ArchiveFormat::detect(&head, &headers);
+        f(Box::new(Cursor::new(head).chain(reader)), format)
+    })
+    .await?;
+
+    // the io error surfaces wrapped in whatever the archive reader made of it
+    if exceeded.load(Ordering::Relaxed) {
+        return Err(ArchiveError::UploadTooLarge(max_upload_size));
+    }
+    result
+}
@@ -35,3 +35,46 @@ pub async fn reset_regions(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
 
     Ok(())
 }
+
+pub async fn create_chat_views(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        CREATE TABLE IF NOT EXISTS chat_views (
+          id INT PRIMARY KEY,
+          total BIGINT NOT NULL
+        )"
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn create_packets(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        CREATE TABLE IF NOT EXISTS packets (
+          id TEXT PRIMARY KEY,
+          saved_at TIMESTAMPTZ NOT NULL
+        )"
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn create_stopwatches(pool: &sqlx::PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        CREATE TABLE IF NOT EXISTS stopwatches (
+          id TEXT PRIMARY KEY,
+          state JSONB NOT NULL,
+          updated_at TIMESTAMPTZ NOT NULL
+        )"
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
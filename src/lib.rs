@@ -0,0 +1,2 @@
+// the day15 password rules, shared by the server and the benchmarks
+pub mod password;
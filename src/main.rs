@@ -1,12 +1,29 @@
+use std::sync::Arc;
+
 use axum::{routing::get, Router};
 use challenge::{
-    day1, day11, day12, day13, day14, day15, day18, day19, day20, day21, day22, day4, day5, day6,
-    day7, day8, day_1,
+    day1,
+    day11::{self, assets::AssetConfig, ImageConfig},
+    day12::{
+        self,
+        timekeeper::{MemoryTimekeeper, PgTimekeeper, Timekeeper},
+        TimekeeperConfig,
+    },
+    day13, day14,
+    day15::{self, PasswordConfig},
+    day18,
+    day19::{self, ChatConfig},
+    day20::{self, ArchiveConfig},
+    day21, day22, day4, day5, day6, day7, day8, day_1,
 };
+use pubsub::{LocalPubSub, PgPubSub, PubSub};
+use shuttle_runtime::SecretStore;
 use sqlx::PgPool;
 
 mod challenge;
 mod db;
+mod pubsub;
+mod util;
 
 #[derive(Clone)]
 struct AppState {
@@ -20,9 +37,28 @@ impl AppState {
 }
 
 #[shuttle_runtime::main]
-async fn main(#[shuttle_shared_db::Postgres] pool: PgPool) -> shuttle_axum::ShuttleAxum {
+async fn main(
+    #[shuttle_shared_db::Postgres] pool: PgPool,
+    #[shuttle_runtime::Secrets] secrets: SecretStore,
+) -> shuttle_axum::ShuttleAxum {
     let state = AppState::new(pool);
 
+    let chat_config = ChatConfig::from_secrets(&secrets)?;
+    // "postgres" shares chat rooms between instances through LISTEN/NOTIFY
+    let pubsub: Arc<dyn PubSub> = match secrets.get("CHAT_PUBSUB").as_deref() {
+        Some("postgres") => {
+            Arc::new(PgPubSub::new(state.pool.clone(), chat_config.capacity).await?)
+        }
+        _ => Arc::new(LocalPubSub::new(chat_config.capacity)),
+    };
+
+    let packets_ttl = TimekeeperConfig::from_secrets(&secrets).ttl;
+    // "postgres" keeps day12 packets across restarts and instances
+    let timekeeper: Arc<dyn Timekeeper> = match secrets.get("PACKETS_STORE").as_deref() {
+        Some("postgres") => Arc::new(PgTimekeeper::new(state.pool.clone(), packets_ttl).await?),
+        _ => Arc::new(MemoryTimekeeper::new(packets_ttl)),
+    };
+
     let router = Router::new()
         .route("/", get(day_1::task1))
         .nest("/-1", day_1::routes())
@@ -32,14 +68,23 @@ async fn main(#[shuttle_shared_db::Postgres] pool: PgPool) -> shuttle_axum::Shut
         .nest("/6", day6::routes())
         .nest("/7", day7::routes())
         .nest("/8", day8::routes())
-        .nest("/11", day11::routes())
-        .nest("/12", day12::routes())
+        .nest(
+            "/11",
+            day11::routes(
+                ImageConfig::from_secrets(&secrets)?,
+                AssetConfig::from_secrets(&secrets)?,
+            ),
+        )
+        .nest("/12", day12::routes(timekeeper))
         .nest("/13", day13::routes(state.clone()))
         .nest("/14", day14::routes())
-        .nest("/15", day15::routes())
+        .nest(
+            "/15",
+            day15::routes(PasswordConfig::from_secrets(&secrets)?),
+        )
         .nest("/18", day18::routes(state.clone()))
-        .nest("/19", day19::routes())
-        .nest("/20", day20::routes())
+        .nest("/19", day19::routes(pubsub, chat_config))
+        .nest("/20", day20::routes(ArchiveConfig::from_secrets(&secrets)?))
         .nest("/21", day21::routes())
         .nest("/22", day22::routes());
 
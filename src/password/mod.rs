@@ -0,0 +1,265 @@
+use std::{collections::HashMap, ops::Range, sync::LazyLock};
+
+use axum::http::StatusCode;
+use itertools::izip;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+pub mod policy;
+
+#[derive(thiserror::Error, Debug)]
+pub enum RuleError {
+    #[error("8 chars")]
+    TooShort,
+    #[error("more types of chars")]
+    NotEnoughCharTypes,
+    #[error("55555")]
+    NotEnoughDigits,
+    #[error("math is hard")]
+    NotAddsUp2023,
+    #[error("numbers too large")]
+    IntegerOverflow,
+    #[error("not joyful enough")]
+    NoJoyOrder,
+    #[error("illegal: no sandwich")]
+    NotRepeatBetween,
+    #[error("outranged")]
+    NoUnicodeInRange,
+    #[error("😳")]
+    NoEmoji,
+    #[error("not a coffee brewer")]
+    IllegalHashEnd,
+    #[error("too predictable")]
+    LowEntropy,
+    #[error("found in the dictionary")]
+    DictionaryWord,
+    #[error("stuttering")]
+    RepeatedSequence,
+    // a rule with its status code or reason replaced by the policy
+    #[error("{reason}")]
+    Policy { status: StatusCode, reason: String },
+}
+
+impl RuleError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RuleError::TooShort
+            | RuleError::NotEnoughCharTypes
+            | RuleError::NotEnoughDigits
+            | RuleError::NotAddsUp2023
+            | RuleError::IntegerOverflow
+            | RuleError::LowEntropy
+            | RuleError::DictionaryWord
+            | RuleError::RepeatedSequence => StatusCode::BAD_REQUEST,
+            RuleError::NoJoyOrder => StatusCode::NOT_ACCEPTABLE,
+            RuleError::NotRepeatBetween => StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            RuleError::NoUnicodeInRange => StatusCode::RANGE_NOT_SATISFIABLE,
+            RuleError::NoEmoji => StatusCode::UPGRADE_REQUIRED,
+            RuleError::IllegalHashEnd => StatusCode::IM_A_TEAPOT,
+            RuleError::Policy { status, .. } => *status,
+        }
+    }
+}
+
+fn validate_length(content: &str, min: usize) -> Result<(), RuleError> {
+    if content.len() < min {
+        return Err(RuleError::TooShort);
+    }
+    Ok(())
+}
+
+static UPPERCASE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[A-Z]").unwrap());
+static LOWERCASE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[a-z]").unwrap());
+static DIGIT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[0-9]").unwrap());
+// ascii only, `\d` would also match digits `parse` doesn't understand
+static INTEGER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[0-9]+").unwrap());
+
+fn validate_char_types(content: &str) -> Result<(), RuleError> {
+    if !UPPERCASE.is_match(content) || !LOWERCASE.is_match(content) || !DIGIT.is_match(content) {
+        return Err(RuleError::NotEnoughCharTypes);
+    }
+    Ok(())
+}
+
+fn validate_digits(content: &str, min: usize) -> Result<(), RuleError> {
+    if content.chars().filter(|c| c.is_numeric()).count() < min {
+        return Err(RuleError::NotEnoughDigits);
+    }
+    Ok(())
+}
+
+fn validate_integers(content: &str, sum: u64) -> Result<(), RuleError> {
+    let mut value: u64 = 0;
+    for mat in INTEGER.find_iter(content) {
+        // only fails on overflow, leading zeros are fine
+        let integer: u64 = mat
+            .as_str()
+            .parse()
+            .map_err(|_| RuleError::IntegerOverflow)?;
+        value = value
+            .checked_add(integer)
+            .ok_or(RuleError::IntegerOverflow)?;
+    }
+    if value != sum {
+        return Err(RuleError::NotAddsUp2023);
+    }
+    Ok(())
+}
+
+fn validate_joy(content: &str) -> Result<(), RuleError> {
+    if let Some(idx) = content.find("y") {
+        if content[idx + 1..].contains("o") || content[idx + 1..].contains("j") {
+            return Err(RuleError::NoJoyOrder);
+        }
+    } else {
+        return Err(RuleError::NoJoyOrder);
+    }
+    if let Some(idx) = content.find("o") {
+        if content[idx + 1..].contains("j") {
+            return Err(RuleError::NoJoyOrder);
+        }
+    } else {
+        return Err(RuleError::NoJoyOrder);
+    }
+    Ok(())
+}
+
+fn validate_repeat(content: &str) -> Result<(), RuleError> {
+    if !izip!(
+        content.chars(),
+        content.chars().skip(1),
+        content.chars().skip(2)
+    )
+    .any(|(a, b, c)| a.is_alphabetic() && b.is_alphabetic() && a == c && b != c)
+    {
+        return Err(RuleError::NotRepeatBetween);
+    }
+    Ok(())
+}
+
+fn validate_unicode(content: &str, range: Range<char>) -> Result<(), RuleError> {
+    if content.chars().all(|c| !range.contains(&c)) {
+        return Err(RuleError::NoUnicodeInRange);
+    }
+    Ok(())
+}
+
+fn validate_emoji(content: &str) -> Result<(), RuleError> {
+    if content
+        .chars()
+        .all(|c| emojis::get(c.to_string().as_str()).is_none())
+    {
+        return Err(RuleError::NoEmoji);
+    }
+    Ok(())
+}
+
+fn validate_sha256_hash(content: &str, suffix: &str) -> Result<(), RuleError> {
+    let hash = Sha256::digest(content);
+    let hex_hash = base16ct::lower::encode_string(&hash);
+    if !hex_hash.ends_with(suffix) {
+        return Err(RuleError::IllegalHashEnd);
+    }
+    Ok(())
+}
+
+// shannon entropy of the chars times the length
+fn entropy_bits(content: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in content.chars() {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+    let len = content.chars().count() as f64;
+    let per_char: f64 = counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    per_char * len
+}
+
+fn validate_entropy(content: &str, bits: f64) -> Result<(), RuleError> {
+    if entropy_bits(content) < bits {
+        return Err(RuleError::LowEntropy);
+    }
+    Ok(())
+}
+
+// a few of the most common password words
+const COMMON_WORDS: &[&str] = &[
+    "password",
+    "passwort",
+    "qwerty",
+    "azerty",
+    "letmein",
+    "welcome",
+    "admin",
+    "login",
+    "master",
+    "dragon",
+    "monkey",
+    "shadow",
+    "sunshine",
+    "princess",
+    "football",
+    "baseball",
+    "iloveyou",
+    "trustno1",
+    "secret",
+    "hello",
+    "freedom",
+    "whatever",
+    "starwars",
+    "superman",
+    "batman",
+    "christmas",
+    "santa",
+];
+
+fn validate_dictionary(
+    content: &str,
+    words: &[String],
+    min_length: usize,
+) -> Result<(), RuleError> {
+    let content = content.to_lowercase();
+    let found = if words.is_empty() {
+        COMMON_WORDS
+            .iter()
+            .any(|word| word.len() >= min_length && content.contains(word))
+    } else {
+        words.iter().any(|word| {
+            word.chars().count() >= min_length && content.contains(&word.to_lowercase())
+        })
+    };
+    if found {
+        return Err(RuleError::DictionaryWord);
+    }
+    Ok(())
+}
+
+fn validate_repeated_sequence(content: &str, length: usize) -> Result<(), RuleError> {
+    let chars: Vec<char> = content.chars().collect();
+    let length = length.max(1);
+    if chars
+        .windows(2 * length)
+        .any(|window| window[..length] == window[length..])
+    {
+        return Err(RuleError::RepeatedSequence);
+    }
+    Ok(())
+}
+
+const SCORE_ENTROPY_BITS: f64 = 80.0;
+
+// 0 to 100, half from the share of passed rules, half from the entropy up to
+// 80 bits
+pub fn score(rules: usize, failed: usize, content: &str) -> u8 {
+    let passed = match rules {
+        0 => 1.0,
+        rules => (rules - failed) as f64 / rules as f64,
+    };
+    let entropy = (entropy_bits(content) / SCORE_ENTROPY_BITS).min(1.0);
+    (50.0 * passed + 50.0 * entropy).round() as u8
+}
@@ -0,0 +1,229 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::Context;
+use axum::http::StatusCode;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use super::{
+    validate_char_types, validate_dictionary, validate_digits, validate_emoji, validate_entropy,
+    validate_integers, validate_joy, validate_length, validate_repeat, validate_repeated_sequence,
+    validate_sha256_hash, validate_unicode, RuleError,
+};
+
+// one check with its parameters, `{"rule": "min_length", "min": 8}`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum Rule {
+    MinLength {
+        min: usize,
+    },
+    // upper and lower case letters and digits
+    CharTypes,
+    MinDigits {
+        min: usize,
+    },
+    // the numbers in the password add up to `sum`
+    IntegerSum {
+        sum: u64,
+    },
+    // j, o and y in this order
+    Joy,
+    // a letter between two identical letters, `aba`
+    Sandwich,
+    // a char in `from..to`, `to` excluded
+    UnicodeRange {
+        from: char,
+        to: char,
+    },
+    Emoji,
+    // the hex sha256 of the password ends with `suffix`
+    HashSuffix {
+        suffix: String,
+    },
+    // shannon entropy of the chars times the length
+    MinEntropy {
+        bits: f64,
+    },
+    // case-insensitive, the built-in list of common words when empty
+    NoDictionaryWord {
+        #[serde(default)]
+        words: Vec<String>,
+        // shorter words are ignored
+        #[serde(default = "default_min_word_length")]
+        min_length: usize,
+    },
+    // `length` chars immediately repeated, `abcabc` or `aaaaaa` for 3
+    NoRepeatedSequence {
+        length: usize,
+    },
+}
+
+fn default_min_word_length() -> usize {
+    4
+}
+
+impl Rule {
+    // as written in policies
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::MinLength { .. } => "min_length",
+            Rule::CharTypes => "char_types",
+            Rule::MinDigits { .. } => "min_digits",
+            Rule::IntegerSum { .. } => "integer_sum",
+            Rule::Joy => "joy",
+            Rule::Sandwich => "sandwich",
+            Rule::UnicodeRange { .. } => "unicode_range",
+            Rule::Emoji => "emoji",
+            Rule::HashSuffix { .. } => "hash_suffix",
+            Rule::MinEntropy { .. } => "min_entropy",
+            Rule::NoDictionaryWord { .. } => "no_dictionary_word",
+            Rule::NoRepeatedSequence { .. } => "no_repeated_sequence",
+        }
+    }
+
+    fn check(&self, content: &str) -> Result<(), RuleError> {
+        match self {
+            Rule::MinLength { min } => validate_length(content, *min),
+            Rule::CharTypes => validate_char_types(content),
+            Rule::MinDigits { min } => validate_digits(content, *min),
+            Rule::IntegerSum { sum } => validate_integers(content, *sum),
+            Rule::Joy => validate_joy(content),
+            Rule::Sandwich => validate_repeat(content),
+            Rule::UnicodeRange { from, to } => validate_unicode(content, *from..*to),
+            Rule::Emoji => validate_emoji(content),
+            Rule::HashSuffix { suffix } => validate_sha256_hash(content, suffix),
+            Rule::MinEntropy { bits } => validate_entropy(content, *bits),
+            Rule::NoDictionaryWord { words, min_length } => {
+                validate_dictionary(content, words, *min_length)
+            }
+            Rule::NoRepeatedSequence { length } => validate_repeated_sequence(content, *length),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RuleSpec {
+    #[serde(flatten)]
+    pub rule: Rule,
+    // replace the rule's own status code and reason when set
+    #[serde(default, deserialize_with = "deserialize_status")]
+    pub status: Option<StatusCode>,
+    pub reason: Option<String>,
+}
+
+fn deserialize_status<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<StatusCode>, D::Error> {
+    let Some(code) = Option::<u16>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    StatusCode::from_u16(code)
+        .ok()
+        .filter(|status| status.is_client_error() || status.is_server_error())
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("{} is not an error status code", code)))
+}
+
+impl RuleSpec {
+    fn new(rule: Rule) -> Self {
+        Self {
+            rule,
+            status: None,
+            reason: None,
+        }
+    }
+
+    pub fn check(&self, content: &str) -> Result<(), RuleError> {
+        self.rule.check(content).map_err(|e| {
+            if self.status.is_none() && self.reason.is_none() {
+                return e;
+            }
+            RuleError::Policy {
+                status: self.status.unwrap_or_else(|| e.status()),
+                reason: self.reason.clone().unwrap_or_else(|| e.to_string()),
+            }
+        })
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Policy {
+    // checked in order
+    pub rules: Vec<RuleSpec>,
+}
+
+impl Default for Policy {
+    // the rules of the original challenge
+    fn default() -> Self {
+        Self {
+            rules: [
+                Rule::MinLength { min: 8 },
+                Rule::CharTypes,
+                Rule::MinDigits { min: 5 },
+                Rule::IntegerSum { sum: 2023 },
+                Rule::Joy,
+                Rule::Sandwich,
+                Rule::UnicodeRange {
+                    from: '\u{2980}',
+                    to: '\u{2BFF}',
+                },
+                Rule::Emoji,
+                Rule::HashSuffix {
+                    suffix: "a".to_string(),
+                },
+            ]
+            .into_iter()
+            .map(RuleSpec::new)
+            .collect(),
+        }
+    }
+}
+
+impl Policy {
+    // `.toml` or `.json` by extension
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(toml::from_str(&content)?),
+            Some("json") => Ok(serde_json::from_str(&content)?),
+            _ => anyhow::bail!("unsupported policy format"),
+        }
+    }
+
+    // the first failing rule decides
+    pub fn check(&self, content: &str) -> Result<(), RuleError> {
+        self.rules.iter().try_for_each(|rule| rule.check(content))
+    }
+
+    // every failing rule, in policy order
+    pub fn violations(&self, content: &str) -> Vec<(&RuleSpec, RuleError)> {
+        self.rules
+            .iter()
+            .filter_map(|rule| rule.check(content).err().map(|e| (rule, e)))
+            .collect()
+    }
+}
+
+pub const DEFAULT_POLICY: &str = "default";
+
+// every `.toml` and `.json` file of `dir` is a policy named after the file,
+// `default` is built in unless a file replaces it
+pub fn load_policies(dir: Option<&Path>) -> anyhow::Result<HashMap<String, Policy>> {
+    let mut policies = HashMap::from([(DEFAULT_POLICY.to_string(), Policy::default())]);
+    let Some(dir) = dir else {
+        return Ok(policies);
+    };
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        let (Some(name), Some("toml" | "json")) = (
+            path.file_stem().and_then(|stem| stem.to_str()),
+            path.extension().and_then(|ext| ext.to_str()),
+        ) else {
+            continue;
+        };
+        let policy =
+            Policy::load(&path).with_context(|| format!("loading policy {}", path.display()))?;
+        policies.insert(name.to_string(), policy);
+    }
+    Ok(policies)
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::sync::{broadcast, Mutex};
+
+use crate::{challenge::day19::TweetMsg, db::create_chat_views};
+
+const NOTIFY_CHANNEL: &str = "cch_chat";
+// tells every instance to drop the views it has not flushed yet
+const RESET_CHANNEL: &str = "cch_chat_reset";
+// views are counted in memory and added to the shared counter this often
+const VIEWS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// fan-out of chat messages per room, plus the shared views counter
+pub trait PubSub: Send + Sync {
+    // `tx` is the sender handed out by `subscribe`, so publishing never takes
+    // the channels lock
+    fn publish(
+        &self,
+        room: u32,
+        tx: &broadcast::Sender<TweetMsg>,
+        msg: TweetMsg,
+    ) -> BoxFuture<'_, anyhow::Result<()>>;
+    fn subscribe(&self, room: u32) -> BoxFuture<'_, Subscription>;
+    // drop the local channel once the last local user left the room, kept
+    // while a receiver is still subscribed
+    fn close(&self, room: u32) -> BoxFuture<'_, ()>;
+    fn add_views(&self, views: u64) -> BoxFuture<'_, anyhow::Result<()>>;
+    fn views(&self) -> BoxFuture<'_, anyhow::Result<u64>>;
+    fn reset(&self) -> BoxFuture<'_, anyhow::Result<()>>;
+}
+
+type Channels = Arc<Mutex<HashMap<u32, broadcast::Sender<TweetMsg>>>>;
+
+// the room's local channel
+pub type Subscription = (broadcast::Sender<TweetMsg>, broadcast::Receiver<TweetMsg>);
+
+async fn subscribe_channel(channels: &Channels, room: u32, capacity: usize) -> Subscription {
+    let mut channels = channels.lock().await;
+    let tx = channels
+        .entry(room)
+        .or_insert_with(|| broadcast::channel(capacity).0);
+    (tx.clone(), tx.subscribe())
+}
+
+async fn close_channel(channels: &Channels, room: u32) {
+    let mut channels = channels.lock().await;
+    if channels
+        .get(&room)
+        .is_some_and(|tx| tx.receiver_count() == 0)
+    {
+        channels.remove(&room);
+    }
+}
+
+// single instance, in-process broadcast channels
+pub struct LocalPubSub {
+    channels: Channels,
+    capacity: usize,
+    total_tweets: AtomicU64,
+}
+
+impl LocalPubSub {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            capacity,
+            total_tweets: AtomicU64::new(0),
+        }
+    }
+}
+
+impl PubSub for LocalPubSub {
+    fn publish(
+        &self,
+        room: u32,
+        tx: &broadcast::Sender<TweetMsg>,
+        msg: TweetMsg,
+    ) -> BoxFuture<'_, anyhow::Result<()>> {
+        // fails once every receiver of the room is gone
+        let result = tx
+            .send(msg)
+            .map(|_| ())
+            .map_err(|_| anyhow::anyhow!("room {} is closed", room));
+        Box::pin(async move { result })
+    }
+
+    fn subscribe(&self, room: u32) -> BoxFuture<'_, Subscription> {
+        Box::pin(subscribe_channel(&self.channels, room, self.capacity))
+    }
+
+    fn close(&self, room: u32) -> BoxFuture<'_, ()> {
+        Box::pin(close_channel(&self.channels, room))
+    }
+
+    fn add_views(&self, views: u64) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.total_tweets.fetch_add(views, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    fn views(&self) -> BoxFuture<'_, anyhow::Result<u64>> {
+        Box::pin(async move { Ok(self.total_tweets.load(Ordering::Relaxed)) })
+    }
+
+    fn reset(&self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.total_tweets.store(0, Ordering::Relaxed);
+            self.channels.lock().await.clear();
+            Ok(())
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    room: u32,
+    #[serde(flatten)]
+    msg: TweetMsg,
+}
+
+// multi instance, messages go through postgres LISTEN/NOTIFY and are
+// re-broadcast to the sockets connected to this instance
+pub struct PgPubSub {
+    pool: PgPool,
+    channels: Channels,
+    capacity: usize,
+    // views not yet added to chat_views
+    pending_views: Arc<AtomicU64>,
+}
+
+impl PgPubSub {
+    pub async fn new(pool: PgPool, capacity: usize) -> anyhow::Result<Self> {
+        create_chat_views(&pool).await?;
+
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen_all([NOTIFY_CHANNEL, RESET_CHANNEL]).await?;
+
+        let channels: Channels = Arc::new(Mutex::new(HashMap::new()));
+        let pending_views = Arc::new(AtomicU64::new(0));
+        tokio::spawn(forward_notifications(
+            listener,
+            channels.clone(),
+            pending_views.clone(),
+        ));
+        tokio::spawn(flush_views_periodically(
+            pool.clone(),
+            pending_views.clone(),
+        ));
+
+        Ok(Self {
+            pool,
+            channels,
+            capacity,
+            pending_views,
+        })
+    }
+}
+
+async fn flush_views(pool: &PgPool, pending_views: &AtomicU64) -> anyhow::Result<()> {
+    let views = pending_views.swap(0, Ordering::Relaxed);
+    if views == 0 {
+        return Ok(());
+    }
+    let result = sqlx::query(
+        "INSERT INTO chat_views (id, total) VALUES (1, $1)
+        ON CONFLICT (id) DO UPDATE SET total = chat_views.total + EXCLUDED.total",
+    )
+    .bind(views as i64)
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        // retried with the next flush
+        pending_views.fetch_add(views, Ordering::Relaxed);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+async fn flush_views_periodically(pool: PgPool, pending_views: Arc<AtomicU64>) {
+    let mut interval = tokio::time::interval(VIEWS_FLUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = flush_views(&pool, &pending_views).await {
+            println!("Error flushing chat views: {}", e);
+        }
+    }
+}
+
+async fn forward_notifications(
+    mut listener: PgListener,
+    channels: Channels,
+    pending_views: Arc<AtomicU64>,
+) {
+    loop {
+        // the listener reconnects by itself on the next recv()
+        let notification = match listener.recv().await {
+            Ok(notification) => notification,
+            Err(e) => {
+                println!("Error receiving chat notification: {}", e);
+                continue;
+            }
+        };
+        if notification.channel() == RESET_CHANNEL {
+            pending_views.store(0, Ordering::Relaxed);
+            continue;
+        }
+        let Ok(envelope) = serde_json::from_str::<Envelope>(notification.payload()) else {
+            continue;
+        };
+        if let Some(tx) = channels.lock().await.get(&envelope.room) {
+            let _ = tx.send(envelope.msg);
+        }
+    }
+}
+
+impl PubSub for PgPubSub {
+    // every instance, this one included, re-broadcasts the notification on its
+    // local channel, `tx` is not used
+    fn publish(
+        &self,
+        room: u32,
+        _tx: &broadcast::Sender<TweetMsg>,
+        msg: TweetMsg,
+    ) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            let payload = serde_json::to_string(&Envelope { room, msg })?;
+            sqlx::query("SELECT pg_notify($1, $2)")
+                .bind(NOTIFY_CHANNEL)
+                .bind(payload)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn subscribe(&self, room: u32) -> BoxFuture<'_, Subscription> {
+        Box::pin(subscribe_channel(&self.channels, room, self.capacity))
+    }
+
+    fn close(&self, room: u32) -> BoxFuture<'_, ()> {
+        Box::pin(close_channel(&self.channels, room))
+    }
+
+    fn add_views(&self, views: u64) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.pending_views.fetch_add(views, Ordering::Relaxed);
+            Ok(())
+        })
+    }
+
+    // other instances' views show up once they flushed
+    fn views(&self) -> BoxFuture<'_, anyhow::Result<u64>> {
+        Box::pin(async move {
+            flush_views(&self.pool, &self.pending_views).await?;
+            let total: Option<i64> =
+                sqlx::query_scalar("SELECT total FROM chat_views WHERE id = 1")
+                    .fetch_optional(&self.pool)
+                    .await?;
+            Ok(total.unwrap_or(0) as u64)
+        })
+    }
+
+    // every instance drops its pending views once the reset commits, a flush
+    // already running on another instance can still land right after it
+    fn reset(&self) -> BoxFuture<'_, anyhow::Result<()>> {
+        Box::pin(async move {
+            self.pending_views.store(0, Ordering::Relaxed);
+            let mut tx = self.pool.begin().await?;
+            sqlx::query("DELETE FROM chat_views")
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("SELECT pg_notify($1, '')")
+                .bind(RESET_CHANNEL)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            self.channels.lock().await.clear();
+            Ok(())
+        })
+    }
+}
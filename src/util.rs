@@ -0,0 +1,34 @@
+use std::{fmt::Display, str::FromStr};
+
+use axum::http::{header, HeaderMap};
+use shuttle_runtime::SecretStore;
+
+// compares secrets without leaking the position of the first difference
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// `default` when the key is unset, a malformed value fails startup with the
+// key name instead of falling back silently
+pub fn parse_secret<T>(secrets: &SecretStore, key: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match secrets.get(key) {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid {} {:?}: {}", key, value, e)),
+        None => Ok(default),
+    }
+}
+
+// declared body size, `None` when missing or malformed so that the streamed
+// limit still applies
+pub fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}